@@ -1,7 +1,7 @@
-use std::{fs, io::BufReader};
+use std::{fs, io::BufReader, process};
 
-use clap::{value_parser, Arg, Command};
-use vdfr::{print_keyvalues, AppInfo, PackageInfo};
+use clap::{value_parser, Arg, ArgAction, Command};
+use vdfr::{print_keyvalues, AppInfo, AppInfoIndex, PackageInfo, VdfrError};
 
 fn main() {
     let matches = Command::new(clap::crate_name!())
@@ -16,7 +16,13 @@ fn main() {
                         .long("id")
                         .value_parser(value_parser!(String)),
                 )
-                .arg(Arg::new("keys").long("keys").value_delimiter(',')),
+                .arg(Arg::new("keys").long("keys").value_delimiter(','))
+                .arg(
+                    Arg::new("verify")
+                        .long("verify")
+                        .action(ArgAction::SetTrue)
+                        .help("Check the stored binary checksums of every app"),
+                ),
         )
         .subcommand(
             Command::new("pkg")
@@ -37,10 +43,18 @@ fn main() {
 
     if let Some(matches) = matches.subcommand_matches("app") {
         let path = matches.get_one::<String>("path").unwrap();
-        let appinfo = read_appinfo(path);
+        if matches.get_flag("verify") {
+            verify_appinfo(path);
+            return;
+        }
+
         if let Some(id) = matches.get_one::<String>("id") {
             let id: u32 = id.parse().expect("Failed to convert ID to u32");
-            let app = appinfo.apps.get(&id);
+            let mut index = AppInfoIndex::from_path(path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+            let app = index
+                .get(id)
+                .unwrap_or_else(|e| panic!("Failed to read app {}: {}", id, e));
             if let Some(app) = app {
                 if let Some(values) = matches.get_many::<String>("keys") {
                     let keys: Vec<&str> = values.map(|s| s.as_str()).collect();
@@ -51,7 +65,11 @@ fn main() {
             } else {
                 eprintln!("Failed to find app with ID {}", id);
             }
-        } else if let Some(keys) = matches.get_many::<String>("keys") {
+            return;
+        }
+
+        let appinfo = read_appinfo(path);
+        if let Some(keys) = matches.get_many::<String>("keys") {
             let keys: Vec<&str> = keys.map(|s| s.as_str()).collect();
             for (id, app) in appinfo.apps {
                 println!("{}: {:?}", id, app.get(&keys));
@@ -93,9 +111,8 @@ fn main() {
 }
 
 fn read_appinfo(path: &str) -> AppInfo {
-    let appinfo_file = fs::File::open(path).unwrap_or_else(|_| panic!("Failed to read {}", path));
-    let mut appinfo_reader = BufReader::new(appinfo_file);
-    let appinfo = vdfr::AppInfo::read(&mut appinfo_reader).unwrap();
+    let appinfo =
+        AppInfo::from_path(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
 
     for (appid, app) in &appinfo.apps {
         println!("{}", appid);
@@ -105,11 +122,41 @@ fn read_appinfo(path: &str) -> AppInfo {
     appinfo
 }
 
+fn verify_appinfo(path: &str) {
+    let appinfo_file = fs::File::open(path).unwrap_or_else(|_| panic!("Failed to read {}", path));
+    let mut appinfo_reader = BufReader::new(appinfo_file);
+    let mismatches = match AppInfo::verify_checksum_bin(&mut appinfo_reader) {
+        Ok(mismatches) => mismatches,
+        Err(VdfrError::NoChecksumBin(_)) => {
+            eprintln!("{} has no checksum_bin values to verify", path);
+            process::exit(1);
+        }
+        Err(e) => panic!("Failed to read {}: {}", path, e),
+    };
+
+    for mismatch in &mismatches {
+        println!(
+            "{}: checksum_bin mismatch, expected {} but found {}",
+            mismatch.app_id,
+            hex(&mismatch.expected),
+            hex(&mismatch.actual)
+        );
+    }
+
+    if mismatches.is_empty() {
+        println!("All checksum_bin values match");
+    } else {
+        process::exit(1);
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 fn read_packageinfo(path: &str) -> PackageInfo {
-    let packageinfo_file =
-        fs::File::open(path).unwrap_or_else(|_| panic!("Failed to read {}", path));
-    let mut packageinfo_reader = BufReader::new(packageinfo_file);
-    let packageinfo = vdfr::PackageInfo::read(&mut packageinfo_reader).unwrap();
+    let packageinfo =
+        PackageInfo::from_path(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
 
     for (packageid, package) in &packageinfo.packages {
         println!("{}", packageid);
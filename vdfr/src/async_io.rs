@@ -0,0 +1,44 @@
+use std::io::Cursor;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{AppInfo, PackageInfo, ReadOptions, VdfrError};
+
+impl AppInfo {
+    pub async fn read_async<R: AsyncRead + Unpin>(reader: &mut R) -> Result<AppInfo, VdfrError> {
+        AppInfo::read_async_with_options(reader, &ReadOptions::default()).await
+    }
+
+    // Reads an appinfo.vdf like `read_with_options`, but from an async
+    // reader. The rest of the input is read into memory first and then parsed
+    // the same way as `read` does, so the file takes up memory alongside the
+    // apps read from it.
+    pub async fn read_async_with_options<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> Result<AppInfo, VdfrError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        AppInfo::read_with_options(&mut Cursor::new(bytes), options)
+    }
+}
+
+impl PackageInfo {
+    pub async fn read_async<R: AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<PackageInfo, VdfrError> {
+        PackageInfo::read_async_with_options(reader, &ReadOptions::default()).await
+    }
+
+    // Reads a packageinfo.vdf like `read_with_options`, but from an async
+    // reader. Packages don't declare their size, so the whole file is read
+    // into memory first.
+    pub async fn read_async_with_options<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> Result<PackageInfo, VdfrError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        PackageInfo::read_with_options(&mut Cursor::new(bytes), options)
+    }
+}
@@ -0,0 +1,609 @@
+use alloc::{borrow::Cow, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, Read, Write},
+};
+
+#[cfg(feature = "std")]
+use byteorder::{LittleEndian, WriteBytesExt};
+
+#[cfg(feature = "std")]
+use crate::{Counter, Key, KeyValues, Value};
+use crate::{VdfrError, MAX_DEPTH};
+
+pub const BIN_NONE: u8 = b'\x00';
+pub const BIN_STRING: u8 = b'\x01';
+pub const BIN_INT32: u8 = b'\x02';
+pub const BIN_FLOAT32: u8 = b'\x03';
+pub const BIN_POINTER: u8 = b'\x04';
+pub const BIN_WIDESTRING: u8 = b'\x05';
+pub const BIN_COLOR: u8 = b'\x06';
+pub const BIN_UINT64: u8 = b'\x07';
+pub const BIN_END: u8 = b'\x08';
+pub const BIN_INT64: u8 = b'\x0A';
+pub const BIN_END_ALT: u8 = b'\x0B';
+
+// Key names stored once and referenced by index from the key-value data, as
+// done by v29 appinfo.vdf.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct StringTable {
+    strings: Vec<Key>,
+    // Index of each string, for looking keys up while writing. Built on the
+    // first insert, as reading has no use for it.
+    indices: Option<HashMap<Key, u32>>,
+}
+
+#[cfg(feature = "std")]
+impl StringTable {
+    pub fn new() -> StringTable {
+        StringTable::default()
+    }
+
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.strings.get(index as usize).map(|s| &**s)
+    }
+
+    // Returns the index of the given string, adding it to the table if it
+    // hasn't been seen before.
+    pub fn insert(&mut self, s: &str) -> u32 {
+        let strings = &self.strings;
+        let indices = self.indices.get_or_insert_with(|| {
+            let mut indices = HashMap::with_capacity(strings.len());
+            for (index, key) in strings.iter().enumerate() {
+                // A stored table may contain duplicates, use the first.
+                indices.entry(key.clone()).or_insert(index as u32);
+            }
+            indices
+        });
+        if let Some(index) = indices.get(s) {
+            return *index;
+        }
+
+        let index = self.strings.len() as u32;
+        let key = Key::from(s);
+        indices.insert(key.clone(), index);
+        self.strings.push(key);
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.strings.iter().map(|s| &**s)
+    }
+
+    pub(crate) fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_u32::<LittleEndian>(self.strings.len() as u32)?;
+        for s in &self.strings {
+            write_string(writer, s, false)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Vec<String>> for StringTable {
+    fn from(strings: Vec<String>) -> Self {
+        strings
+            .into_iter()
+            .map(Key::from)
+            .collect::<Vec<Key>>()
+            .into()
+    }
+}
+
+// Keeps the strings as given, even if they happen to contain duplicates.
+#[cfg(feature = "std")]
+impl From<Vec<Key>> for StringTable {
+    fn from(strings: Vec<Key>) -> Self {
+        StringTable {
+            strings,
+            indices: None,
+        }
+    }
+}
+
+// Receives the contents of binary key-values as `visit` reads them, so that
+// they can be inspected without building a KeyValues tree. Every method does
+// nothing by default, leaving only the interesting ones to implement. Keys and
+// strings are borrowed from the input or string table for `'de` where they
+// can be, so visitors that keep them don't always have to copy them.
+pub trait KvVisitor<'de> {
+    // Called for a nested node, before its contents.
+    fn begin_object(&mut self, _key: Cow<'de, str>) {}
+
+    // Called after the contents of the most recently begun node.
+    fn end_object(&mut self) {}
+
+    fn string(&mut self, _key: Cow<'de, str>, _value: Cow<'de, str>) {}
+
+    fn wide_string(&mut self, _key: Cow<'de, str>, _value: Cow<'de, str>) {}
+
+    fn int32(&mut self, _key: Cow<'de, str>, _value: i32) {}
+
+    fn pointer(&mut self, _key: Cow<'de, str>, _value: i32) {}
+
+    fn color(&mut self, _key: Cow<'de, str>, _value: i32) {}
+
+    fn uint64(&mut self, _key: Cow<'de, str>, _value: u64) {}
+
+    fn int64(&mut self, _key: Cow<'de, str>, _value: i64) {}
+
+    fn float32(&mut self, _key: Cow<'de, str>, _value: f32) {}
+}
+
+// Reads a binary key-value node, up to and including its terminator. With
+// `alt_format` the node ends with BIN_END_ALT instead of BIN_END. When a
+// string table is given, keys are read as indices into it rather than as
+// inline strings. Offsets in errors are relative to where reading started.
+#[cfg(feature = "std")]
+pub fn read<R: Read>(
+    reader: &mut R,
+    alt_format: bool,
+    string_table: Option<&StringTable>,
+) -> Result<KeyValues, VdfrError> {
+    read_with_keys(
+        reader,
+        alt_format,
+        string_table,
+        &mut Keys::new(string_table),
+    )
+}
+
+// Reads like `read`, sharing keys with everything else read with `keys`.
+#[cfg(feature = "std")]
+pub(crate) fn read_with_keys<R: Read>(
+    reader: &mut R,
+    alt_format: bool,
+    string_table: Option<&StringTable>,
+    keys: &mut Keys,
+) -> Result<KeyValues, VdfrError> {
+    let mut builder = TreeBuilder {
+        keys,
+        node: KeyValues::new(),
+        parents: Vec::new(),
+    };
+    visit(reader, alt_format, string_table, &mut builder)?;
+    Ok(builder.node)
+}
+
+// Reads a binary key-value node like `read`, passing its contents to the
+// visitor instead of returning them. The node itself isn't reported, only
+// what's inside it.
+#[cfg(feature = "std")]
+pub fn visit<'de, R: Read, V: KvVisitor<'de> + ?Sized>(
+    reader: &mut R,
+    alt_format: bool,
+    string_table: Option<&'de StringTable>,
+    visitor: &mut V,
+) -> Result<(), VdfrError> {
+    visit_node(
+        &mut Counter::new(reader, 0),
+        alt_format,
+        string_table,
+        visitor,
+    )
+}
+
+// The binary key-value parser behind `read`, `visit` and borrowed::read,
+// which differ only in where they read from and what they build.
+pub(crate) fn visit_node<'de, S, T, V>(
+    source: &mut S,
+    alt_format: bool,
+    string_table: Option<T>,
+    visitor: &mut V,
+) -> Result<(), VdfrError>
+where
+    S: Source<'de>,
+    T: KeyTable<'de>,
+    V: KvVisitor<'de> + ?Sized,
+{
+    visit_nested(source, alt_format, string_table, visitor, 0)
+}
+
+// Reads a node `depth` levels below the one visit_node started with.
+fn visit_nested<'de, S, T, V>(
+    source: &mut S,
+    alt_format: bool,
+    string_table: Option<T>,
+    visitor: &mut V,
+    depth: usize,
+) -> Result<(), VdfrError>
+where
+    S: Source<'de>,
+    T: KeyTable<'de>,
+    V: KvVisitor<'de> + ?Sized,
+{
+    let current_bin_end = if alt_format { BIN_END_ALT } else { BIN_END };
+
+    loop {
+        let offset = source.position();
+        let t = source.read_u8().map_err(|e| e.at_offset(offset))?;
+        if t == current_bin_end {
+            return Ok(());
+        }
+
+        let key = read_key(source, string_table).map_err(|e| e.at_offset(offset))?;
+        visit_value(source, t, key, alt_format, string_table, visitor, depth)
+            .map_err(|e| e.at_offset(offset))?;
+    }
+}
+
+fn read_key<'de, S: Source<'de>, T: KeyTable<'de>>(
+    source: &mut S,
+    string_table: Option<T>,
+) -> Result<Cow<'de, str>, VdfrError> {
+    if let Some(string_table) = string_table {
+        let string_table_index = source.read_u32()?;
+        string_table
+            .key(string_table_index)
+            .ok_or(VdfrError::StringTableIndexOutOfRange(string_table_index))
+    } else {
+        source.read_string()
+    }
+}
+
+fn visit_value<'de, S, T, V>(
+    source: &mut S,
+    t: u8,
+    key: Cow<'de, str>,
+    alt_format: bool,
+    string_table: Option<T>,
+    visitor: &mut V,
+    depth: usize,
+) -> Result<(), VdfrError>
+where
+    S: Source<'de>,
+    T: KeyTable<'de>,
+    V: KvVisitor<'de> + ?Sized,
+{
+    if t == BIN_NONE {
+        if depth == MAX_DEPTH {
+            return Err(VdfrError::NestingTooDeep.in_key(&key));
+        }
+        visitor.begin_object(key.clone());
+        visit_nested(source, alt_format, string_table, visitor, depth + 1)
+            .map_err(|e| e.in_key(&key))?;
+        visitor.end_object();
+        Ok(())
+    } else if t == BIN_STRING {
+        visit_scalar(key, source.read_string(), |k, v| visitor.string(k, v))
+    } else if t == BIN_WIDESTRING {
+        visit_scalar(key, source.read_wide_string(), |k, v| {
+            visitor.wide_string(k, v)
+        })
+    } else if t == BIN_INT32 {
+        visit_scalar(key, source.read_i32(), |k, v| visitor.int32(k, v))
+    } else if t == BIN_POINTER {
+        visit_scalar(key, source.read_i32(), |k, v| visitor.pointer(k, v))
+    } else if t == BIN_COLOR {
+        visit_scalar(key, source.read_i32(), |k, v| visitor.color(k, v))
+    } else if t == BIN_UINT64 {
+        visit_scalar(key, source.read_u64(), |k, v| visitor.uint64(k, v))
+    } else if t == BIN_INT64 {
+        visit_scalar(key, source.read_i64(), |k, v| visitor.int64(k, v))
+    } else if t == BIN_FLOAT32 {
+        visit_scalar(key, source.read_f32(), |k, v| visitor.float32(k, v))
+    } else {
+        Err(VdfrError::InvalidType(t).in_key(&key))
+    }
+}
+
+// Hands the key over along with the value once it has been read, keeping
+// it for the error otherwise.
+fn visit_scalar<'de, T>(
+    key: Cow<'de, str>,
+    value: Result<T, VdfrError>,
+    visit: impl FnOnce(Cow<'de, str>, T),
+) -> Result<(), VdfrError> {
+    match value {
+        Ok(value) => {
+            visit(key, value);
+            Ok(())
+        }
+        Err(e) => Err(e.in_key(&key)),
+    }
+}
+
+// Builds the tree that `read` returns out of the calls `visit` makes.
+#[cfg(feature = "std")]
+struct TreeBuilder<'k> {
+    keys: &'k mut Keys,
+    node: KeyValues,
+    // The nodes enclosing `node`, along with the key it goes under.
+    parents: Vec<(KeyValues, Key)>,
+}
+
+#[cfg(feature = "std")]
+impl TreeBuilder<'_> {
+    fn push(&mut self, key: Cow<str>, value: Value) {
+        let key = self.keys.get(&key);
+        self.node.push(key, value);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> KvVisitor<'de> for TreeBuilder<'_> {
+    fn begin_object(&mut self, key: Cow<'de, str>) {
+        let key = self.keys.get(&key);
+        let parent = core::mem::take(&mut self.node);
+        self.parents.push((parent, key));
+    }
+
+    fn end_object(&mut self) {
+        if let Some((parent, key)) = self.parents.pop() {
+            let node = core::mem::replace(&mut self.node, parent);
+            self.node.push(key, Value::KeyValueType(node));
+        }
+    }
+
+    fn string(&mut self, key: Cow<'de, str>, value: Cow<'de, str>) {
+        self.push(key, Value::StringType(value.into_owned()));
+    }
+
+    fn wide_string(&mut self, key: Cow<'de, str>, value: Cow<'de, str>) {
+        self.push(key, Value::WideStringType(value.into_owned()));
+    }
+
+    fn int32(&mut self, key: Cow<'de, str>, value: i32) {
+        self.push(key, Value::Int32Type(value));
+    }
+
+    fn pointer(&mut self, key: Cow<'de, str>, value: i32) {
+        self.push(key, Value::PointerType(value));
+    }
+
+    fn color(&mut self, key: Cow<'de, str>, value: i32) {
+        self.push(key, Value::ColorType(value));
+    }
+
+    fn uint64(&mut self, key: Cow<'de, str>, value: u64) {
+        self.push(key, Value::UInt64Type(value));
+    }
+
+    fn int64(&mut self, key: Cow<'de, str>, value: i64) {
+        self.push(key, Value::Int64Type(value));
+    }
+
+    fn float32(&mut self, key: Cow<'de, str>, value: f32) {
+        self.push(key, Value::Float32Type(value));
+    }
+}
+
+// Hands out one shared Key per distinct key name, so that a whole file read
+// with the same Keys holds each name once rather than once per app.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub(crate) struct Keys(HashSet<Key>);
+
+#[cfg(feature = "std")]
+impl Keys {
+    // Starts out with the strings of the given string table, so that keys
+    // read through it are the table's own copies.
+    pub(crate) fn new(string_table: Option<&StringTable>) -> Keys {
+        match string_table {
+            Some(string_table) => Keys(string_table.strings.iter().cloned().collect()),
+            None => Keys::default(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Key {
+        if let Some(key) = self.0.get(key) {
+            return key.clone();
+        }
+        let key = Key::from(key);
+        self.0.insert(key.clone());
+        key
+    }
+}
+
+// Looks up keys by their index in a string table.
+pub(crate) trait KeyTable<'de>: Copy {
+    fn key(self, index: u32) -> Option<Cow<'de, str>>;
+}
+
+#[cfg(feature = "std")]
+impl<'de> KeyTable<'de> for &'de StringTable {
+    fn key(self, index: u32) -> Option<Cow<'de, str>> {
+        self.get(index).map(Cow::Borrowed)
+    }
+}
+
+impl<'de> KeyTable<'de> for &[Cow<'de, str>] {
+    fn key(self, index: u32) -> Option<Cow<'de, str>> {
+        self.get(index as usize).cloned()
+    }
+}
+
+// Where `visit_node` reads from. Values are little-endian.
+pub(crate) trait Source<'de> {
+    fn position(&self) -> u64;
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], VdfrError>;
+
+    // Reads a null-terminated string, replacing invalid UTF-8.
+    fn read_string(&mut self) -> Result<Cow<'de, str>, VdfrError>;
+
+    fn read_wide_string(&mut self) -> Result<Cow<'de, str>, VdfrError> {
+        let mut buf: Vec<u16> = Vec::new();
+        loop {
+            // Maybe this should be big-endian?
+            let c = u16::from_le_bytes(self.read_array()?);
+            if c == 0 {
+                break;
+            }
+            buf.push(c);
+        }
+        Ok(Cow::Owned(String::from_utf16_lossy(&buf)))
+    }
+
+    fn read_u8(&mut self) -> Result<u8, VdfrError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, VdfrError> {
+        self.read_array().map(u32::from_le_bytes)
+    }
+
+    fn read_i32(&mut self) -> Result<i32, VdfrError> {
+        self.read_array().map(i32::from_le_bytes)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, VdfrError> {
+        self.read_array().map(u64::from_le_bytes)
+    }
+
+    fn read_i64(&mut self) -> Result<i64, VdfrError> {
+        self.read_array().map(i64::from_le_bytes)
+    }
+
+    fn read_f32(&mut self) -> Result<f32, VdfrError> {
+        self.read_array().map(f32::from_le_bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: Read> Source<'de> for Counter<R> {
+    fn position(&self) -> u64 {
+        Counter::position(self)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], VdfrError> {
+        let mut array = [0; N];
+        self.read_exact(&mut array)?;
+        Ok(array)
+    }
+
+    fn read_string(&mut self) -> Result<Cow<'de, str>, VdfrError> {
+        let mut buf: Vec<u8> = Vec::new();
+        loop {
+            let [c] = Source::read_array(self)?;
+            if c == 0 {
+                break;
+            }
+            buf.push(c);
+        }
+        Ok(Cow::Owned(String::from_utf8(buf).unwrap_or_else(|e| {
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        })))
+    }
+}
+
+// Reads from a slice, handing out sub-slices rather than copying.
+pub(crate) struct SliceReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        SliceReader { bytes, position: 0 }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VdfrError> {
+        let rest = self.rest();
+        if rest.len() < len {
+            return Err(VdfrError::UnexpectedEof);
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+}
+
+impl<'a> Source<'a> for SliceReader<'a> {
+    fn position(&self) -> u64 {
+        self.position as u64
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], VdfrError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_string(&mut self) -> Result<Cow<'a, str>, VdfrError> {
+        let len = self
+            .rest()
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(VdfrError::UnexpectedEof)?;
+        let s = self.take(len)?;
+        self.take(1)?;
+        Ok(String::from_utf8_lossy(s))
+    }
+}
+
+// Writes a binary key-value node followed by its terminator, the inverse of
+// `read`. Keys missing from the string table are added to it.
+#[cfg(feature = "std")]
+pub fn write<W: Write>(
+    writer: &mut W,
+    key_values: &KeyValues,
+    alt_format: bool,
+    mut string_table: Option<&mut StringTable>,
+) -> Result<(), VdfrError> {
+    let current_bin_end = if alt_format { BIN_END_ALT } else { BIN_END };
+
+    for (key, value) in key_values {
+        let t = match value {
+            Value::KeyValueType(_) => BIN_NONE,
+            Value::StringType(_) => BIN_STRING,
+            Value::WideStringType(_) => BIN_WIDESTRING,
+            Value::Int32Type(_) => BIN_INT32,
+            Value::PointerType(_) => BIN_POINTER,
+            Value::ColorType(_) => BIN_COLOR,
+            Value::UInt64Type(_) => BIN_UINT64,
+            Value::Int64Type(_) => BIN_INT64,
+            Value::Float32Type(_) => BIN_FLOAT32,
+        };
+        writer.write_u8(t)?;
+
+        if let Some(string_table) = string_table.as_deref_mut() {
+            writer.write_u32::<LittleEndian>(string_table.insert(key))?;
+        } else {
+            write_string(writer, key, false)?;
+        }
+
+        match value {
+            Value::KeyValueType(subnode) => {
+                write(writer, subnode, alt_format, string_table.as_deref_mut())?
+            }
+            Value::StringType(s) => write_string(writer, s, false)?,
+            Value::WideStringType(s) => write_string(writer, s, true)?,
+            Value::Int32Type(val) | Value::PointerType(val) | Value::ColorType(val) => {
+                writer.write_i32::<LittleEndian>(*val)?
+            }
+            Value::UInt64Type(val) => writer.write_u64::<LittleEndian>(*val)?,
+            Value::Int64Type(val) => writer.write_i64::<LittleEndian>(*val)?,
+            Value::Float32Type(val) => writer.write_f32::<LittleEndian>(*val)?,
+        }
+    }
+
+    writer.write_u8(current_bin_end)?;
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+fn write_string<W: Write>(writer: &mut W, s: &str, wide: bool) -> Result<(), Error> {
+    if wide {
+        for c in s.encode_utf16() {
+            writer.write_u16::<LittleEndian>(c)?;
+        }
+        writer.write_u16::<LittleEndian>(0)
+    } else {
+        writer.write_all(s.as_bytes())?;
+        writer.write_u8(0)
+    }
+}
@@ -0,0 +1,412 @@
+#[cfg(feature = "std")]
+use alloc::string::String;
+use alloc::{borrow::Cow, vec::Vec};
+
+use crate::{
+    binary::{self, KvVisitor, SliceReader, Source},
+    VdfrError,
+};
+#[cfg(feature = "std")]
+use crate::{AppInfoLayout, Entry, IndexMap};
+
+// Counterparts of the crate's Value, KeyValues, App and AppInfo that borrow
+// their strings from the buffer they were parsed from, such as an in-memory
+// or memory-mapped appinfo.vdf. Strings that are valid UTF-8 aren't copied,
+// and v29 keys all point into the one string table. Use `into_owned` to get
+// the usual types back. Key-value decoding here doesn't need the `std`
+// feature, only AppInfo does.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'a> {
+    StringType(Cow<'a, str>),
+    // Always owned, as the buffer holds UTF-16.
+    WideStringType(Cow<'a, str>),
+    Int32Type(i32),
+    PointerType(i32),
+    ColorType(i32),
+    UInt64Type(u64),
+    Int64Type(i64),
+    Float32Type(f32),
+    KeyValueType(KeyValues<'a>),
+}
+
+impl Value<'_> {
+    pub fn into_owned(self) -> crate::Value {
+        match self {
+            Value::StringType(s) => crate::Value::StringType(s.into_owned()),
+            Value::WideStringType(s) => crate::Value::WideStringType(s.into_owned()),
+            Value::Int32Type(val) => crate::Value::Int32Type(val),
+            Value::PointerType(val) => crate::Value::PointerType(val),
+            Value::ColorType(val) => crate::Value::ColorType(val),
+            Value::UInt64Type(val) => crate::Value::UInt64Type(val),
+            Value::Int64Type(val) => crate::Value::Int64Type(val),
+            Value::Float32Type(val) => crate::Value::Float32Type(val),
+            Value::KeyValueType(kv) => crate::Value::KeyValueType(kv.into_owned()),
+        }
+    }
+}
+
+// An ordered list of key-value pairs, allowing duplicate keys like
+// crate::KeyValues.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyValues<'a>(Vec<(Cow<'a, str>, Value<'a>)>);
+
+impl<'a> KeyValues<'a> {
+    pub fn new() -> Self {
+        KeyValues(Vec::new())
+    }
+
+    // Returns the first value with the given key.
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_all<'s>(&'s self, key: &'s str) -> impl Iterator<Item = &'s Value<'a>> + 's {
+        self.0.iter().filter(move |(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, (Cow<'a, str>, Value<'a>)> {
+        self.0.iter()
+    }
+
+    pub fn into_owned(self) -> crate::KeyValues {
+        self.0
+            .into_iter()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect()
+    }
+}
+
+impl<'s, 'a> IntoIterator for &'s KeyValues<'a> {
+    type Item = &'s (Cow<'a, str>, Value<'a>);
+    type IntoIter = core::slice::Iter<'s, (Cow<'a, str>, Value<'a>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for KeyValues<'a> {
+    type Item = (Cow<'a, str>, Value<'a>);
+    type IntoIter = alloc::vec::IntoIter<(Cow<'a, str>, Value<'a>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+fn find_keys<'s, 'a>(kv: &'s KeyValues<'a>, keys: &[&str]) -> Option<&'s Value<'a>> {
+    let (key, rest) = keys.split_first()?;
+    let value = kv.get(key);
+    if rest.is_empty() {
+        value
+    } else if let Some(Value::KeyValueType(kv)) = value {
+        find_keys(kv, rest)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct App<'a> {
+    pub size: u32,
+    pub state: u32,
+    pub last_update: u32,
+    pub access_token: u64,
+    pub checksum_txt: [u8; 20],
+    pub checksum_bin: Option<[u8; 20]>,
+    pub change_number: u32,
+    pub key_values: KeyValues<'a>,
+}
+
+impl<'a> App<'a> {
+    pub fn get(&self, keys: &[&str]) -> Option<&Value<'a>> {
+        find_keys(&self.key_values, keys)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_owned(self) -> crate::App {
+        crate::App {
+            size: self.size,
+            state: self.state,
+            last_update: self.last_update,
+            access_token: self.access_token,
+            checksum_txt: self.checksum_txt,
+            checksum_bin: self.checksum_bin,
+            change_number: self.change_number,
+            key_values: self.key_values.into_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct AppInfo<'a> {
+    pub magic: u32,
+    pub universe: u32,
+    pub apps: IndexMap<u32, App<'a>>,
+    // The v29 string table, kept so that `into_owned` can keep it too.
+    pub string_table: Vec<Cow<'a, str>>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> AppInfo<'a> {
+    // Parses a whole appinfo.vdf held in memory. Offsets in errors are
+    // relative to the start of `bytes`.
+    pub fn parse(bytes: &'a [u8]) -> Result<AppInfo<'a>, VdfrError> {
+        let mut reader = SliceReader::new(bytes);
+
+        let magic = reader.read_u32()?;
+        let layout = AppInfoLayout::for_magic(magic)?;
+        let universe = reader.read_u32()?;
+
+        let string_table = if layout.string_table {
+            let field_offset = reader.position();
+            let offset = reader.read_i64().map_err(|e| e.at_offset(field_offset))?;
+            let offset = crate::AppInfo::string_table_offset(offset)
+                .map_err(|e| e.at_offset(field_offset))?;
+            Some(read_string_table(bytes, offset).map_err(|e| e.at_offset(offset))?)
+        } else {
+            None
+        };
+
+        let mut apps = IndexMap::new();
+
+        loop {
+            let offset = reader.position();
+            let app_id = reader.read_u32().map_err(|e| e.at_offset(offset))?;
+            if app_id == 0 {
+                break;
+            }
+
+            let app = read_app(&mut reader, layout, string_table.as_deref())
+                .map_err(|e| e.at_offset(offset).in_entry(Entry::App(app_id)))?;
+            apps.insert(app_id, app);
+        }
+
+        Ok(AppInfo {
+            magic,
+            universe,
+            apps,
+            string_table: string_table.unwrap_or_default(),
+        })
+    }
+
+    pub fn into_owned(self) -> crate::AppInfo {
+        crate::AppInfo {
+            magic: self.magic,
+            universe: self.universe,
+            apps: self
+                .apps
+                .into_iter()
+                .map(|(id, app)| (id, app.into_owned()))
+                .collect(),
+            string_table: self
+                .string_table
+                .into_iter()
+                .map(crate::Key::from)
+                .collect::<Vec<_>>()
+                .into(),
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn read_app<'a>(
+    reader: &mut SliceReader<'a>,
+    layout: &AppInfoLayout,
+    string_table: Option<&[Cow<'a, str>]>,
+) -> Result<App<'a>, VdfrError> {
+    let size = reader.read_u32()?;
+    let state = reader.read_u32()?;
+    let last_update = reader.read_u32()?;
+    let access_token = reader.read_u64()?;
+    let checksum_txt = reader.read_array()?;
+    let change_number = reader.read_u32()?;
+    let checksum_bin = if layout.checksum_bin {
+        Some(reader.read_array()?)
+    } else {
+        None
+    };
+
+    let key_values = read_node(reader, false, string_table)?;
+
+    Ok(App {
+        size,
+        state,
+        last_update,
+        access_token,
+        checksum_txt,
+        checksum_bin,
+        change_number,
+        key_values,
+    })
+}
+
+// Splits the string table at `offset` into slices of `bytes`.
+#[cfg(feature = "std")]
+fn read_string_table(bytes: &[u8], offset: u64) -> Result<Vec<Cow<'_, str>>, VdfrError> {
+    let bytes = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| bytes.get(offset..))
+        .ok_or(VdfrError::UnexpectedEof)?;
+    Ok(crate::AppInfo::split_string_table(bytes)?
+        .into_iter()
+        .map(String::from_utf8_lossy)
+        .collect())
+}
+
+// Parses a binary key-value node from the start of `bytes`, the borrowed
+// counterpart of binary::read. Returns the node along with the number of
+// bytes it took up.
+pub fn read<'a>(
+    bytes: &'a [u8],
+    alt_format: bool,
+    string_table: Option<&[Cow<'a, str>]>,
+) -> Result<(KeyValues<'a>, usize), VdfrError> {
+    let mut reader = SliceReader::new(bytes);
+    let node = read_node(&mut reader, alt_format, string_table)?;
+    Ok((node, reader.position() as usize))
+}
+
+fn read_node<'a>(
+    reader: &mut SliceReader<'a>,
+    alt_format: bool,
+    string_table: Option<&[Cow<'a, str>]>,
+) -> Result<KeyValues<'a>, VdfrError> {
+    let mut builder = TreeBuilder::default();
+    binary::visit_node(reader, alt_format, string_table, &mut builder)?;
+    Ok(builder.node)
+}
+
+// Builds borrowed key-values out of the calls binary::visit_node makes.
+#[derive(Default)]
+struct TreeBuilder<'a> {
+    node: KeyValues<'a>,
+    // The nodes enclosing `node`, along with the key it goes under.
+    parents: Vec<(KeyValues<'a>, Cow<'a, str>)>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn push(&mut self, key: Cow<'a, str>, value: Value<'a>) {
+        self.node.0.push((key, value));
+    }
+}
+
+impl<'a> KvVisitor<'a> for TreeBuilder<'a> {
+    fn begin_object(&mut self, key: Cow<'a, str>) {
+        let parent = core::mem::take(&mut self.node);
+        self.parents.push((parent, key));
+    }
+
+    fn end_object(&mut self) {
+        if let Some((parent, key)) = self.parents.pop() {
+            let node = core::mem::replace(&mut self.node, parent);
+            self.node.0.push((key, Value::KeyValueType(node)));
+        }
+    }
+
+    fn string(&mut self, key: Cow<'a, str>, value: Cow<'a, str>) {
+        self.push(key, Value::StringType(value));
+    }
+
+    fn wide_string(&mut self, key: Cow<'a, str>, value: Cow<'a, str>) {
+        self.push(key, Value::WideStringType(value));
+    }
+
+    fn int32(&mut self, key: Cow<'a, str>, value: i32) {
+        self.push(key, Value::Int32Type(value));
+    }
+
+    fn pointer(&mut self, key: Cow<'a, str>, value: i32) {
+        self.push(key, Value::PointerType(value));
+    }
+
+    fn color(&mut self, key: Cow<'a, str>, value: i32) {
+        self.push(key, Value::ColorType(value));
+    }
+
+    fn uint64(&mut self, key: Cow<'a, str>, value: u64) {
+        self.push(key, Value::UInt64Type(value));
+    }
+
+    fn int64(&mut self, key: Cow<'a, str>, value: i64) {
+        self.push(key, Value::Int64Type(value));
+    }
+
+    fn float32(&mut self, key: Cow<'a, str>, value: f32) {
+        self.push(key, Value::Float32Type(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{BIN_END, BIN_END_ALT, BIN_INT32, BIN_NONE, BIN_STRING, BIN_UINT64};
+    use alloc::vec;
+
+    // Doesn't rely on std, so it also runs with `--no-default-features`.
+    #[test]
+    fn read_nested_node() {
+        let mut bytes = vec![BIN_NONE];
+        bytes.extend(b"common\0");
+        bytes.push(BIN_STRING);
+        bytes.extend(b"name\0x\0");
+        bytes.push(BIN_INT32);
+        bytes.extend(b"id\0");
+        bytes.extend(440i32.to_le_bytes());
+        bytes.push(BIN_UINT64);
+        bytes.extend(b"id\0");
+        bytes.extend(7u64.to_le_bytes());
+        bytes.push(BIN_END);
+        bytes.push(BIN_END);
+        bytes.extend(b"trailing");
+
+        let (node, len) = read(&bytes, false, None).unwrap();
+        assert_eq!(len, bytes.len() - 8);
+        let Some(Value::KeyValueType(common)) = node.get("common") else {
+            panic!("common should be a node");
+        };
+        assert_eq!(common.get("name"), Some(&Value::StringType("x".into())));
+        assert!(matches!(
+            common.get("name"),
+            Some(Value::StringType(Cow::Borrowed(_)))
+        ));
+        assert_eq!(
+            common.get_all("id").collect::<Vec<_>>(),
+            [&Value::Int32Type(440), &Value::UInt64Type(7)]
+        );
+    }
+
+    #[test]
+    fn read_keys_from_string_table() {
+        let string_table = [Cow::Borrowed("appid"), Cow::Borrowed("name")];
+        let mut bytes = vec![BIN_STRING];
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(b"x\0");
+        bytes.push(BIN_END_ALT);
+
+        let (node, len) = read(&bytes, true, Some(&string_table)).unwrap();
+        assert_eq!(len, bytes.len());
+        assert_eq!(node.get("name"), Some(&Value::StringType("x".into())));
+
+        bytes[1] = 2;
+        assert!(matches!(
+            read(&bytes, true, Some(&string_table)).unwrap_err().inner(),
+            VdfrError::StringTableIndexOutOfRange(2)
+        ));
+    }
+}
@@ -0,0 +1,547 @@
+use std::io::Read;
+
+use serde::de::{
+    self, value::BorrowedStrDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess,
+    SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize};
+
+use crate::{binary, binary::StringTable, Key, KeyValues, Value, VdfrError};
+
+impl de::Error for VdfrError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        VdfrError::SerdeError(msg.to_string())
+    }
+}
+
+// Deserializes a type from a key-value node, such as `App::key_values`.
+pub fn from_key_values<'de, T: Deserialize<'de>>(
+    key_values: &'de KeyValues,
+) -> Result<T, VdfrError> {
+    T::deserialize(KeyValuesDeserializer(key_values))
+}
+
+pub fn from_value<'de, T: Deserialize<'de>>(value: &'de Value) -> Result<T, VdfrError> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+// Reads a binary key-value node, as `binary::read` does, and deserializes a
+// type from it.
+pub fn from_reader<R: Read, T: DeserializeOwned>(
+    reader: &mut R,
+    alt_format: bool,
+    string_table: Option<&StringTable>,
+) -> Result<T, VdfrError> {
+    let key_values = binary::read(reader, alt_format, string_table)?;
+    from_key_values(&key_values)
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $ty:ty,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+                match self.0.trim().parse::<$ty>() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(
+                        Unexpected::Str(self.0),
+                        &stringify!($ty),
+                    )),
+                }
+            }
+        )*
+    };
+}
+
+// Strings are used for keys and for most values, including numeric ones in
+// text KeyValues, so numbers and booleans are parsed out of them on request.
+struct StrDeserializer<'de>(&'de str);
+
+impl<'de> de::Deserializer<'de> for StrDeserializer<'de> {
+    type Error = VdfrError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        match self.0.trim() {
+            "1" | "true" => visitor.visit_bool(true),
+            "0" | "false" => visitor.visit_bool(false),
+            _ => Err(de::Error::invalid_value(
+                Unexpected::Str(self.0),
+                &"a boolean",
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        visitor.visit_enum(BorrowedStrDeserializer::new(self.0))
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct KeyValuesDeserializer<'de>(&'de KeyValues);
+
+impl<'de> de::Deserializer<'de> for KeyValuesDeserializer<'de> {
+    type Error = VdfrError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        visitor.visit_map(KeyValuesAccess {
+            iter: self.0.iter(),
+            value: None,
+        })
+    }
+
+    // Valve stores arrays as nodes keyed "0", "1", ..., so sequences are
+    // made out of the values in order and the keys are ignored.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        visitor.visit_seq(KeyValuesAccess {
+            iter: self.0.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Enum variants with content are nodes with a single key naming the
+    // variant.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        match self.0.iter().as_slice() {
+            [(variant, value)] => visitor.visit_enum(EnumDeserializer { variant, value }),
+            _ => Err(de::Error::invalid_value(
+                Unexpected::Map,
+                &"a node with a single key",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+}
+
+struct KeyValuesAccess<'de> {
+    iter: std::slice::Iter<'de, (Key, Value)>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> MapAccess<'de> for KeyValuesAccess<'de> {
+    type Error = VdfrError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, VdfrError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(StrDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, VdfrError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(ValueDeserializer(value)),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+impl<'de> SeqAccess<'de> for KeyValuesAccess<'de> {
+    type Error = VdfrError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, VdfrError> {
+        match self.iter.next() {
+            Some((_, value)) => seed.deserialize(ValueDeserializer(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumDeserializer<'de> {
+    variant: &'de str,
+    value: &'de Value,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer<'de> {
+    type Error = VdfrError;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), VdfrError> {
+        let variant = seed.deserialize(StrDeserializer(self.variant))?;
+        Ok((variant, ValueDeserializer(self.value)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = VdfrError;
+
+    fn unit_variant(self) -> Result<(), VdfrError> {
+        Err(de::Error::invalid_type(
+            self.unexpected(),
+            &"a unit variant",
+        ))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, VdfrError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+struct ValueDeserializer<'de>(&'de Value);
+
+impl<'de> ValueDeserializer<'de> {
+    fn unexpected(&self) -> Unexpected<'de> {
+        match self.0 {
+            Value::StringType(s) | Value::WideStringType(s) => Unexpected::Str(s),
+            Value::Int32Type(v) | Value::PointerType(v) | Value::ColorType(v) => {
+                Unexpected::Signed(*v as i64)
+            }
+            Value::UInt64Type(v) => Unexpected::Unsigned(*v),
+            Value::Int64Type(v) => Unexpected::Signed(*v),
+            Value::Float32Type(v) => Unexpected::Float(*v as f64),
+            Value::KeyValueType(_) => Unexpected::Map,
+        }
+    }
+}
+
+// Strings and nodes are handed off to their own deserializers, which know how
+// to interpret the type hints. Numbers are always visited as what they are,
+// and serde's own visitors convert them as needed.
+macro_rules! deserialize_delegated {
+    ($($method:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+                match self.0 {
+                    Value::StringType(s) | Value::WideStringType(s) => {
+                        StrDeserializer(s).$method(visitor)
+                    }
+                    Value::KeyValueType(kv) => KeyValuesDeserializer(kv).$method(visitor),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = VdfrError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        match self.0 {
+            Value::StringType(s) | Value::WideStringType(s) => visitor.visit_borrowed_str(s),
+            Value::Int32Type(v) | Value::PointerType(v) | Value::ColorType(v) => {
+                visitor.visit_i32(*v)
+            }
+            Value::UInt64Type(v) => visitor.visit_u64(*v),
+            Value::Int64Type(v) => visitor.visit_i64(*v),
+            Value::Float32Type(v) => visitor.visit_f32(*v),
+            Value::KeyValueType(kv) => KeyValuesDeserializer(kv).deserialize_any(visitor),
+        }
+    }
+
+    deserialize_delegated! {
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_seq,
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        match self.0 {
+            Value::StringType(s) | Value::WideStringType(s) => {
+                StrDeserializer(s).deserialize_bool(visitor)
+            }
+            Value::Int32Type(v) => visitor.visit_bool(*v != 0),
+            Value::UInt64Type(v) => visitor.visit_bool(*v != 0),
+            Value::Int64Type(v) => visitor.visit_bool(*v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Binary KeyValues are loose about types, so numbers are accepted where
+    // strings are expected.
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        match self.0 {
+            Value::Int32Type(v) | Value::PointerType(v) | Value::ColorType(v) => {
+                visitor.visit_string(v.to_string())
+            }
+            Value::UInt64Type(v) => visitor.visit_string(v.to_string()),
+            Value::Int64Type(v) => visitor.visit_string(v.to_string()),
+            Value::Float32Type(v) => visitor.visit_string(v.to_string()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, VdfrError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, VdfrError> {
+        match self.0 {
+            Value::StringType(s) | Value::WideStringType(s) => {
+                StrDeserializer(s).deserialize_enum(name, variants, visitor)
+            }
+            Value::KeyValueType(kv) => {
+                KeyValuesDeserializer(kv).deserialize_enum(name, variants, visitor)
+            }
+            _ => Err(de::Error::invalid_type(self.unexpected(), &"an enum")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AppInfo {
+        appid: u32,
+        common: Common,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Common {
+        name: String,
+        r#type: String,
+        oslist: String,
+    }
+
+    // Binary key-values mixing string and integer values, as in appinfo.vdf.
+    fn app_key_values() -> KeyValues {
+        let mut common = KeyValues::new();
+        common.push("name", Value::StringType("Team Fortress 2".to_string()));
+        common.push("type", Value::StringType("Game".to_string()));
+        common.push("oslist", Value::StringType("windows,macos".to_string()));
+        let mut appinfo = KeyValues::new();
+        appinfo.push("appid", Value::Int32Type(440));
+        appinfo.push("common", Value::KeyValueType(common));
+        let mut key_values = KeyValues::new();
+        key_values.push("appinfo", Value::KeyValueType(appinfo));
+        key_values
+    }
+
+    #[test]
+    fn deserialize_app_key_values() {
+        let expected = AppInfo {
+            appid: 440,
+            common: Common {
+                name: "Team Fortress 2".to_string(),
+                r#type: "Game".to_string(),
+                oslist: "windows,macos".to_string(),
+            },
+        };
+
+        let key_values = app_key_values();
+        let appinfo: AppInfo = from_value(key_values.get("appinfo").unwrap()).unwrap();
+        assert_eq!(appinfo, expected);
+
+        let mut bytes = Vec::new();
+        binary::write(&mut bytes, &key_values, false, None).unwrap();
+        let read: std::collections::HashMap<String, AppInfo> =
+            from_reader(&mut bytes.as_slice(), false, None).unwrap();
+        assert_eq!(read["appinfo"], expected);
+    }
+
+    #[test]
+    fn deserialize_numbers_and_booleans_from_strings() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Fields {
+            appid: u32,
+            offset: i64,
+            scale: f32,
+            enabled: bool,
+            hidden: bool,
+            name: String,
+        }
+
+        let key_values = text::parse(
+            r#""appid" "440" "offset" "-5" "scale" "1.5" "enabled" "1" "hidden" "0" "name" "42""#,
+        )
+        .unwrap();
+        let fields: Fields = from_key_values(&key_values).unwrap();
+        assert_eq!(
+            fields,
+            Fields {
+                appid: 440,
+                offset: -5,
+                scale: 1.5,
+                enabled: true,
+                hidden: false,
+                name: "42".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_vec_from_numbered_node() {
+        #[derive(Debug, Deserialize)]
+        struct Package {
+            appids: Vec<u32>,
+        }
+
+        let key_values = text::parse(r#""appids" { "0" "440" "1" "570" "2" "730" }"#).unwrap();
+        let package: Package = from_key_values(&key_values).unwrap();
+        assert_eq!(package.appids, [440, 570, 730]);
+    }
+
+    #[test]
+    fn deserialize_type_mismatch() {
+        type Map<T> = std::collections::HashMap<String, T>;
+
+        let key_values = text::parse(r#""appid" "abc""#).unwrap();
+        let err = from_key_values::<Map<u32>>(&key_values).unwrap_err();
+        assert!(matches!(&err, VdfrError::SerdeError(message) if message.contains("\"abc\"")));
+
+        let key_values = text::parse(r#""appid" { "0" "1" }"#).unwrap();
+        assert!(from_key_values::<Map<u32>>(&key_values).is_err());
+
+        let key_values = text::parse(r#""enabled" "yes""#).unwrap();
+        assert!(from_key_values::<Map<bool>>(&key_values).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{
+    binary::{Keys, StringTable},
+    App, AppInfo, AppInfoHeader, AppInfoLayout, Counter, Entry, IndexMap, VdfrError,
+};
+
+// An appinfo.vdf that has only had its app headers scanned. Each app's
+// key-values are parsed when it's looked up, which is much faster than
+// `AppInfo::read` when only a few apps are needed.
+pub struct AppInfoIndex<R> {
+    reader: R,
+    pub magic: u32,
+    pub universe: u32,
+    layout: &'static AppInfoLayout,
+    string_table: Option<StringTable>,
+    keys: Keys,
+    // Stream position the scan started from.
+    start: u64,
+    // Offset of each app's entry, relative to `start`.
+    offsets: IndexMap<u32, u64>,
+}
+
+impl AppInfoIndex<BufReader<File>> {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, VdfrError> {
+        AppInfoIndex::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> AppInfoIndex<R> {
+    // Scans the apps of the given reader, using their declared sizes to skip
+    // over their key-values.
+    pub fn new(mut reader: R) -> Result<Self, VdfrError> {
+        let start = reader.stream_position()?;
+        let mut counter = Counter::from_current(&mut reader)?;
+        let AppInfoHeader {
+            magic,
+            universe,
+            layout,
+            string_table,
+        } = AppInfo::read_header(&mut counter)?;
+
+        let mut offsets = IndexMap::new();
+
+        loop {
+            let offset = counter.position();
+            let app_id = counter
+                .read_u32::<LittleEndian>()
+                .map_err(|e| VdfrError::from(e).at_offset(offset))?;
+            if app_id == 0 {
+                break;
+            }
+
+            let size = counter.read_u32::<LittleEndian>().map_err(|e| {
+                VdfrError::from(e)
+                    .at_offset(offset)
+                    .in_entry(Entry::App(app_id))
+            })?;
+            offsets.insert(app_id, offset);
+            counter.seek(SeekFrom::Current(size as i64))?;
+        }
+
+        Ok(AppInfoIndex {
+            reader,
+            magic,
+            universe,
+            layout,
+            keys: Keys::new(string_table.as_ref()),
+            string_table,
+            start,
+            offsets,
+        })
+    }
+
+    // Parses the app with the given ID, if there is one.
+    pub fn get(&mut self, app_id: u32) -> Result<Option<App>, VdfrError> {
+        let offset = match self.offsets.get(&app_id) {
+            Some(offset) => *offset,
+            None => return Ok(None),
+        };
+
+        // AppInfo::read_app expects the ID to have been read already.
+        self.reader.seek(SeekFrom::Start(self.start + offset + 4))?;
+        let mut counter = Counter::new(&mut self.reader, offset + 4);
+        let app = AppInfo::read_app(
+            &mut counter,
+            self.layout,
+            self.string_table.as_ref(),
+            &mut self.keys,
+        )
+        .map_err(|e| e.at_offset(offset).in_entry(Entry::App(app_id)))?;
+        Ok(Some(app))
+    }
+}
+
+impl<R> AppInfoIndex<R> {
+    pub fn contains(&self, app_id: u32) -> bool {
+        self.offsets.contains_key(&app_id)
+    }
+
+    // The IDs of all apps, in file order.
+    pub fn app_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.offsets.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
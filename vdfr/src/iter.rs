@@ -0,0 +1,154 @@
+use std::io::{Read, Seek};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{
+    binary::{Keys, StringTable},
+    App, AppInfo, AppInfoHeader, AppInfoLayout, Counter, Entry, Package, PackageInfo,
+    PackageInfoLayout, VdfrError,
+};
+
+// Reads the apps of an appinfo.vdf one at a time, in file order, without
+// keeping the ones already returned around. Stops after the first error.
+pub struct AppIter<R> {
+    reader: Counter<R>,
+    pub magic: u32,
+    pub universe: u32,
+    layout: &'static AppInfoLayout,
+    string_table: Option<StringTable>,
+    keys: Keys,
+    done: bool,
+}
+
+impl<R: Read + Seek> AppIter<R> {
+    pub(crate) fn new(reader: R) -> Result<Self, VdfrError> {
+        let mut reader = Counter::from_current(reader)?;
+        let AppInfoHeader {
+            magic,
+            universe,
+            layout,
+            string_table,
+        } = AppInfo::read_header(&mut reader)?;
+
+        Ok(AppIter {
+            reader,
+            magic,
+            universe,
+            layout,
+            keys: Keys::new(string_table.as_ref()),
+            string_table,
+            done: false,
+        })
+    }
+}
+
+impl<R: Read> Iterator for AppIter<R> {
+    type Item = Result<(u32, App), VdfrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let offset = self.reader.position();
+        let result = self
+            .reader
+            .read_u32::<LittleEndian>()
+            .map_err(|e| VdfrError::from(e).at_offset(offset))
+            .and_then(|app_id| {
+                if app_id == 0 {
+                    return Ok(None);
+                }
+                AppInfo::read_app(
+                    &mut self.reader,
+                    self.layout,
+                    self.string_table.as_ref(),
+                    &mut self.keys,
+                )
+                .map(|app| Some((app_id, app)))
+                .map_err(|e| e.at_offset(offset).in_entry(Entry::App(app_id)))
+            });
+
+        match result {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for AppIter<R> {}
+
+// Reads the packages of a packageinfo.vdf one at a time, in file order, up
+// to the 0xffffffff terminator. Stops after the first error.
+pub struct PackageIter<R> {
+    reader: Counter<R>,
+    pub magic: u32,
+    pub universe: u32,
+    layout: &'static PackageInfoLayout,
+    keys: Keys,
+    done: bool,
+}
+
+impl<R: Read> PackageIter<R> {
+    pub(crate) fn new(reader: R) -> Result<Self, VdfrError> {
+        let mut reader = Counter::new(reader, 0);
+
+        let magic = reader.read_u32::<LittleEndian>()?;
+        let layout = PackageInfoLayout::for_magic(magic)?;
+        let universe = reader.read_u32::<LittleEndian>()?;
+
+        Ok(PackageIter {
+            reader,
+            magic,
+            universe,
+            layout,
+            keys: Keys::default(),
+            done: false,
+        })
+    }
+}
+
+impl<R: Read> Iterator for PackageIter<R> {
+    type Item = Result<(u32, Package), VdfrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let offset = self.reader.position();
+        let result = self
+            .reader
+            .read_u32::<LittleEndian>()
+            .map_err(|e| VdfrError::from(e).at_offset(offset))
+            .and_then(|package_id| {
+                if package_id == 0xffffffff {
+                    return Ok(None);
+                }
+                PackageInfo::read_package(&mut self.reader, self.layout, &mut self.keys)
+                    .map(|package| Some((package_id, package)))
+                    .map_err(|e| e.at_offset(offset).in_entry(Entry::Package(package_id)))
+            });
+
+        match result {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for PackageIter<R> {}
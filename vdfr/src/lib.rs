@@ -1,52 +1,331 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Without the default `std` feature only the key-value types and the slice
+// based decoding in `borrowed` are available.
+
+extern crate alloc;
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
 use std::{
-    collections::HashMap,
     fs::File,
-    io::{BufReader, Error, Read, Seek},
+    io::{BufReader, Cursor, Read, Seek, Write},
+    path::Path,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "std")]
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+pub use indexmap::IndexMap;
+#[cfg(feature = "std")]
+use sha1::{Digest, Sha1};
+
+#[cfg(feature = "async")]
+mod async_io;
+pub mod binary;
+pub mod borrowed;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod iter;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "std")]
+pub mod text;
+
+#[cfg(feature = "std")]
+use binary::{Keys, KvVisitor, StringTable};
+#[cfg(feature = "std")]
+pub use index::AppInfoIndex;
+#[cfg(feature = "std")]
+pub use iter::{AppIter, PackageIter};
+
+// How deeply nodes may be nested before parsing fails, so that malicious
+// input can't overflow the stack.
+const MAX_DEPTH: usize = 512;
 
-const BIN_NONE: u8 = b'\x00';
-const BIN_STRING: u8 = b'\x01';
-const BIN_INT32: u8 = b'\x02';
-const BIN_FLOAT32: u8 = b'\x03';
-const BIN_POINTER: u8 = b'\x04';
-const BIN_WIDESTRING: u8 = b'\x05';
-const BIN_COLOR: u8 = b'\x06';
-const BIN_UINT64: u8 = b'\x07';
-const BIN_END: u8 = b'\x08';
-const BIN_INT64: u8 = b'\x0A';
-const BIN_END_ALT: u8 = b'\x0B';
+#[cfg(feature = "std")]
+const APPINFO_VERSION_26: u32 = 0x7564426;
+#[cfg(feature = "std")]
+const APPINFO_VERSION_27: u32 = 0x7564427;
+#[cfg(feature = "std")]
+const APPINFO_VERSION_28: u32 = 0x7564428;
+#[cfg(feature = "std")]
+const APPINFO_VERSION_29: u32 = 0x7564429;
 
-const VERSION_28: u32 = 0x7564428;
-const VERSION_29: u32 = 0x7564429;
+// How each known version of appinfo.vdf lays out its apps.
+#[cfg(feature = "std")]
+struct AppInfoLayout {
+    magic: u32,
+    // Whether entries carry the SHA-1 of their binary key-values.
+    checksum_bin: bool,
+    // Whether keys are stored in a string table at the end of the file.
+    string_table: bool,
+}
+
+#[cfg(feature = "std")]
+const APPINFO_LAYOUTS: &[AppInfoLayout] = &[
+    AppInfoLayout {
+        magic: APPINFO_VERSION_26,
+        checksum_bin: false,
+        string_table: false,
+    },
+    AppInfoLayout {
+        magic: APPINFO_VERSION_27,
+        checksum_bin: false,
+        string_table: false,
+    },
+    AppInfoLayout {
+        magic: APPINFO_VERSION_28,
+        checksum_bin: true,
+        string_table: false,
+    },
+    AppInfoLayout {
+        magic: APPINFO_VERSION_29,
+        checksum_bin: true,
+        string_table: true,
+    },
+];
+
+#[cfg(feature = "std")]
+impl AppInfoLayout {
+    fn for_magic(magic: u32) -> Result<&'static AppInfoLayout, VdfrError> {
+        APPINFO_LAYOUTS
+            .iter()
+            .find(|layout| layout.magic == magic)
+            .ok_or(VdfrError::UnsupportedVersion(magic))
+    }
+}
+
+// The fields at the start of an appinfo.vdf, before its apps.
+#[cfg(feature = "std")]
+struct AppInfoHeader {
+    magic: u32,
+    universe: u32,
+    layout: &'static AppInfoLayout,
+    string_table: Option<StringTable>,
+}
+
+#[cfg(feature = "std")]
+const PACKAGEINFO_VERSION_27: u32 = 0x6565527;
+#[cfg(feature = "std")]
+const PACKAGEINFO_VERSION_28: u32 = 0x6565528;
+
+// How each known version of packageinfo.vdf lays out its packages.
+#[cfg(feature = "std")]
+struct PackageInfoLayout {
+    magic: u32,
+    // Whether entries carry the `pics` field.
+    pics: bool,
+}
+
+#[cfg(feature = "std")]
+const PACKAGEINFO_LAYOUTS: &[PackageInfoLayout] = &[
+    PackageInfoLayout {
+        magic: PACKAGEINFO_VERSION_27,
+        pics: false,
+    },
+    PackageInfoLayout {
+        magic: PACKAGEINFO_VERSION_28,
+        pics: true,
+    },
+];
+
+#[cfg(feature = "std")]
+impl PackageInfoLayout {
+    fn for_magic(magic: u32) -> Result<&'static PackageInfoLayout, VdfrError> {
+        PACKAGEINFO_LAYOUTS
+            .iter()
+            .find(|layout| layout.magic == magic)
+            .ok_or(VdfrError::UnsupportedVersion(magic))
+    }
+}
 
 #[derive(Debug)]
 pub enum VdfrError {
     UnsupportedVersion(u32),
     InvalidType(u8),
+    InvalidSize(u32),
+    StringTableIndexOutOfRange(u32),
+    InvalidStringTableOffset(i64),
+    // The version, which predates binary checksums.
+    NoChecksumBin(u32),
+    StringTableCountMismatch {
+        expected: u32,
+        actual: usize,
+    },
+    UnexpectedEof,
+    NestingTooDeep,
+    #[cfg(feature = "std")]
     ReadError(std::io::Error),
+    SyntaxError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    SerdeError(String),
+    // Another error, along with where in the input it happened.
+    Context(ErrorContext, Box<VdfrError>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Entry {
+    App(u32),
+    Package(u32),
+}
+
+#[derive(Debug, Default)]
+pub struct ErrorContext {
+    // Offset of the entry or key-value that failed to parse, relative to where
+    // reading started. For readers that aren't at their start, this isn't the
+    // same as their stream position.
+    pub offset: Option<u64>,
+    pub entry: Option<Entry>,
+    // Keys leading up to the value that failed to parse.
+    pub path: Vec<String>,
+}
+
+impl VdfrError {
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            VdfrError::Context(context, _) => Some(context),
+            _ => None,
+        }
+    }
+
+    // Returns the underlying error, without its context.
+    pub fn inner(&self) -> &VdfrError {
+        match self {
+            VdfrError::Context(_, e) => e,
+            e => e,
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut ErrorContext {
+        if !matches!(self, VdfrError::Context(..)) {
+            let e = core::mem::replace(self, VdfrError::UnexpectedEof);
+            *self = VdfrError::Context(ErrorContext::default(), Box::new(e));
+        }
+        match self {
+            VdfrError::Context(context, _) => context,
+            _ => unreachable!(),
+        }
+    }
+
+    // Records the offset of the item being parsed, unless a more precise one
+    // was already recorded further down.
+    pub(crate) fn at_offset(mut self, offset: u64) -> VdfrError {
+        self.context_mut().offset.get_or_insert(offset);
+        self
+    }
+
+    // Makes an offset relative to a nested reader relative to its parent.
+    #[cfg(feature = "std")]
+    pub(crate) fn rebase(mut self, base: u64) -> VdfrError {
+        if let VdfrError::Context(context, _) = &mut self {
+            context.offset = context.offset.map(|offset| offset + base);
+        }
+        self
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn in_entry(mut self, entry: Entry) -> VdfrError {
+        self.context_mut().entry = Some(entry);
+        self
+    }
+
+    // Prepends a key to the path, as errors bubble up through nested nodes.
+    pub(crate) fn in_key(mut self, key: &str) -> VdfrError {
+        self.context_mut().path.insert(0, key.to_string());
+        self
+    }
+}
+
+impl core::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        match self.entry {
+            Some(Entry::App(id)) => parts.push(format!("app {}", id)),
+            Some(Entry::Package(id)) => parts.push(format!("package {}", id)),
+            None => {}
+        }
+        parts.extend(self.path.iter().cloned());
+        write!(f, "{}", parts.join(" > "))?;
+
+        if let Some(offset) = self.offset {
+            if !parts.is_empty() {
+                write!(f, " ")?;
+            }
+            write!(f, "at offset {:#x}", offset)?;
+        }
+        Ok(())
+    }
 }
 
-impl std::error::Error for VdfrError {}
+#[cfg(feature = "std")]
+impl std::error::Error for VdfrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VdfrError::ReadError(e) => Some(e),
+            VdfrError::Context(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
-impl std::fmt::Display for VdfrError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for VdfrError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             VdfrError::UnsupportedVersion(v) => write!(f, "Invalid version {:#x}", v),
             VdfrError::InvalidType(t) => write!(f, "Invalid type {:#x}", t),
+            VdfrError::InvalidSize(s) => write!(f, "Invalid size {}", s),
+            VdfrError::StringTableIndexOutOfRange(i) => {
+                write!(f, "String table index {} out of range", i)
+            }
+            VdfrError::InvalidStringTableOffset(o) => {
+                write!(f, "Invalid string table offset {}", o)
+            }
+            VdfrError::NoChecksumBin(v) => write!(f, "Version {:#x} has no binary checksums", v),
+            VdfrError::StringTableCountMismatch { expected, actual } => write!(
+                f,
+                "String table should have {} strings, but has {}",
+                expected, actual
+            ),
+            VdfrError::UnexpectedEof => write!(f, "Unexpected end of file"),
+            VdfrError::NestingTooDeep => write!(f, "Nodes are nested too deeply"),
+            #[cfg(feature = "std")]
             VdfrError::ReadError(e) => e.fmt(f),
+            VdfrError::SyntaxError {
+                line,
+                column,
+                message,
+            } => write!(f, "{} at line {}, column {}", message, line, column),
+            VdfrError::SerdeError(e) => e.fmt(f),
+            VdfrError::Context(context, e) => write!(f, "{}: {}", context, e),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for VdfrError {
     fn from(e: std::io::Error) -> Self {
-        VdfrError::ReadError(e)
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            VdfrError::UnexpectedEof
+        } else {
+            VdfrError::ReadError(e)
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Value {
     StringType(String),
     WideStringType(String),
@@ -59,18 +338,146 @@ pub enum Value {
     KeyValueType(KeyValues),
 }
 
-type KeyValues = HashMap<String, Value>;
+// Keys are reference counted so that every occurrence of a key in a file can
+// share one copy, which for v29 files is the string table's own.
+pub type Key = Arc<str>;
+
+// An ordered list of key-value pairs. Keys are kept in the order they were
+// read in and may repeat, as they legitimately do in text KeyValues.
+#[derive(Clone, Default, PartialEq)]
+pub struct KeyValues(Vec<(Key, Value)>);
+
+impl KeyValues {
+    pub fn new() -> KeyValues {
+        KeyValues::default()
+    }
+
+    // Returns the value of the first entry with the given key.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.iter().find(|(k, _)| &**k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.0.iter_mut().find(|(k, _)| &**k == key).map(|(_, v)| v)
+    }
+
+    // Returns the values of all entries with the given key, in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Value> + 'a {
+        self.0
+            .iter()
+            .filter(move |(k, _)| &**k == key)
+            .map(|(_, v)| v)
+    }
+
+    // Replaces the value of the first entry with the given key, returning the
+    // old value, or appends a new entry if there is none.
+    pub fn insert(&mut self, key: impl Into<Key>, value: Value) -> Option<Value> {
+        let key = key.into();
+        if let Some(v) = self.get_mut(&key) {
+            return Some(core::mem::replace(v, value));
+        }
+        self.0.push((key, value));
+        None
+    }
+
+    // Appends an entry, even if the key is already present.
+    pub fn push(&mut self, key: impl Into<Key>, value: Value) {
+        self.0.push((key.into(), value));
+    }
+
+    // Removes all entries with the given key, returning the first value.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let mut removed = None;
+        let mut i = 0;
+        while i < self.0.len() {
+            if &*self.0[i].0 == key {
+                let (_, v) = self.0.remove(i);
+                removed.get_or_insert(v);
+            } else {
+                i += 1;
+            }
+        }
+        removed
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.iter().any(|(k, _)| &**k == key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, (Key, Value)> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, (Key, Value)> {
+        self.0.iter_mut()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(k, _)| &**k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.0.iter().map(|(_, v)| v)
+    }
+}
+
+impl core::fmt::Debug for KeyValues {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a KeyValues {
+    type Item = &'a (Key, Value);
+    type IntoIter = core::slice::Iter<'a, (Key, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for KeyValues {
+    type Item = (Key, Value);
+    type IntoIter = alloc::vec::IntoIter<(Key, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<K: Into<Key>> FromIterator<(K, Value)> for KeyValues {
+    fn from_iter<I: IntoIterator<Item = (K, Value)>>(iter: I) -> Self {
+        KeyValues(iter.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+}
+
+impl<K: Into<Key>> Extend<(K, Value)> for KeyValues {
+    fn extend<I: IntoIterator<Item = (K, Value)>>(&mut self, iter: I) {
+        self.0.extend(iter.into_iter().map(|(k, v)| (k.into(), v)))
+    }
+}
 
 // Recursively search for the specified sequence of keys in the key-value data.
 // The order of the keys dictates the hierarchy, with all except the last having
 // to be a Value::KeyValueType.
+#[cfg(feature = "std")]
 fn find_keys<'a>(kv: &'a KeyValues, keys: &[&str]) -> Option<&'a Value> {
     if keys.is_empty() {
         return None;
     }
 
     let key = keys.first().unwrap();
-    let value = kv.get(&key.to_string());
+    let value = kv.get(key);
     if keys.len() == 1 {
         value
     } else if let Some(Value::KeyValueType(kv)) = value {
@@ -80,252 +487,743 @@ fn find_keys<'a>(kv: &'a KeyValues, keys: &[&str]) -> Option<&'a Value> {
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct App {
     pub size: u32,
     pub state: u32,
     pub last_update: u32,
     pub access_token: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::hex"))]
     pub checksum_txt: [u8; 20],
-    pub checksum_bin: [u8; 20],
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::hex_option"))]
+    pub checksum_bin: Option<[u8; 20]>,
     pub change_number: u32,
     pub key_values: KeyValues,
 }
 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct ReadOptions {
+    // Skip entries that fail to parse, recording their errors in
+    // `diagnostics`, rather than failing the whole read.
+    pub recover: bool,
+}
+
+// One of the ways of reading the apps that follow an appinfo.vdf's header.
+#[cfg(feature = "std")]
+type ReadApps<R> = fn(
+    &mut Counter<R>,
+    &AppInfoLayout,
+    Option<&StringTable>,
+    &ReadOptions,
+    &mut AppInfo,
+) -> Result<(), VdfrError>;
+
+#[cfg(feature = "std")]
 #[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub app_id: u32,
+    pub expected: [u8; 20],
+    pub actual: [u8; 20],
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AppInfo {
     pub magic: u32,
     pub universe: u32,
-    pub apps: HashMap<u32, App>,
+    pub apps: IndexMap<u32, App>,
+    // The string table of a v29 file as read, so that writing it back keeps
+    // every key at its original index. Empty for other versions.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub string_table: StringTable,
+    // Errors for the entries skipped when reading with `ReadOptions::recover`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub diagnostics: Vec<VdfrError>,
 }
 
+#[cfg(feature = "std")]
 impl AppInfo {
-    pub fn read(reader: &mut BufReader<File>) -> Result<AppInfo, VdfrError> {
-        let magic = reader.read_u32::<LittleEndian>()?;
+    pub fn from_bytes(bytes: &[u8]) -> Result<AppInfo, VdfrError> {
+        AppInfo::read(&mut Cursor::new(bytes))
+    }
 
-        if ![VERSION_28, VERSION_29].contains(&magic) {
-            return Err(VdfrError::UnsupportedVersion(magic));
-        }
+    pub fn from_path(path: impl AsRef<Path>) -> Result<AppInfo, VdfrError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        AppInfo::read(&mut reader)
+    }
 
-        let universe = reader.read_u32::<LittleEndian>()?;
+    // Reads the header of an appinfo.vdf, returning an iterator over its apps
+    // in file order. Unlike `read`, apps are parsed as the iterator is
+    // advanced and not kept, so iteration can stop early.
+    pub fn iter_apps<R: Read + Seek>(reader: R) -> Result<AppIter<R>, VdfrError> {
+        AppIter::new(reader)
+    }
 
-        let string_table = if magic == VERSION_29 {
-            Some(AppInfo::read_string_table(reader)?)
-        } else {
-            None
-        };
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<AppInfo, VdfrError> {
+        AppInfo::read_with_options(reader, &ReadOptions::default())
+    }
+
+    pub fn read_with_options<R: Read + Seek>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> Result<AppInfo, VdfrError> {
+        AppInfo::read_with(reader, options, AppInfo::read_apps)
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn read_par<R: Read + Seek>(reader: &mut R) -> Result<AppInfo, VdfrError> {
+        AppInfo::read_par_with_options(reader, &ReadOptions::default())
+    }
+
+    // Reads like `read_with_options`, but parses the apps' key-values in
+    // parallel. Every entry is located by its declared size first, so unlike
+    // `read`, an entry whose size doesn't match its key-values fails to read.
+    #[cfg(feature = "rayon")]
+    pub fn read_par_with_options<R: Read + Seek>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> Result<AppInfo, VdfrError> {
+        AppInfo::read_with(reader, options, AppInfo::read_apps_par)
+    }
+
+    // Reads the header, then leaves the apps to `read_apps`.
+    fn read_with<R: Read + Seek>(
+        reader: R,
+        options: &ReadOptions,
+        read_apps: ReadApps<R>,
+    ) -> Result<AppInfo, VdfrError> {
+        let mut reader = Counter::from_current(reader)?;
+        let AppInfoHeader {
+            magic,
+            universe,
+            layout,
+            string_table,
+        } = AppInfo::read_header(&mut reader)?;
 
         let mut appinfo = AppInfo {
             universe,
             magic,
-            apps: HashMap::new(),
+            apps: IndexMap::new(),
+            string_table: StringTable::new(),
+            diagnostics: Vec::new(),
         };
 
+        read_apps(
+            &mut reader,
+            layout,
+            string_table.as_ref(),
+            options,
+            &mut appinfo,
+        )?;
+        appinfo.string_table = string_table.unwrap_or_default();
+
+        Ok(appinfo)
+    }
+
+    // Reads the apps up to the terminating zero ID.
+    fn read_apps<R: Read>(
+        reader: &mut Counter<R>,
+        layout: &AppInfoLayout,
+        string_table: Option<&StringTable>,
+        options: &ReadOptions,
+        appinfo: &mut AppInfo,
+    ) -> Result<(), VdfrError> {
+        let mut keys = Keys::new(string_table);
+
         loop {
-            let app_id = reader.read_u32::<LittleEndian>()?;
+            let offset = reader.position();
+            let app_id = match reader.read_u32::<LittleEndian>() {
+                Ok(0) => break,
+                Ok(app_id) => app_id,
+                Err(e) if options.recover => {
+                    appinfo
+                        .diagnostics
+                        .push(VdfrError::from(e).at_offset(offset));
+                    break;
+                }
+                Err(e) => return Err(VdfrError::from(e).at_offset(offset)),
+            };
+
+            if !options.recover {
+                let app = AppInfo::read_app(reader, layout, string_table, &mut keys)
+                    .map_err(|e| e.at_offset(offset).in_entry(Entry::App(app_id)))?;
+                appinfo.apps.insert(app_id, app);
+                continue;
+            }
+
+            // Read the whole entry by its declared size first, so that the
+            // next one can still be found if its key-values turn out to be
+            // corrupt. If the entry itself is broken there's nowhere to go.
+            let (mut app, key_values) = match AppInfo::read_raw_app(reader, layout) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    appinfo
+                        .diagnostics
+                        .push(e.at_offset(offset).in_entry(Entry::App(app_id)));
+                    break;
+                }
+            };
+
+            let key_values_offset = reader.position() - key_values.len() as u64;
+            match binary::read_with_keys(&mut key_values.as_slice(), false, string_table, &mut keys)
+            {
+                Ok(key_values) => {
+                    app.key_values = key_values;
+                    appinfo.apps.insert(app_id, app);
+                }
+                Err(e) => appinfo.diagnostics.push(
+                    e.rebase(key_values_offset)
+                        .at_offset(offset)
+                        .in_entry(Entry::App(app_id)),
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Locates every entry by its declared size, then parses their key-values
+    // in parallel. Apps and diagnostics still end up in file order.
+    #[cfg(feature = "rayon")]
+    fn read_apps_par<R: Read>(
+        reader: &mut Counter<R>,
+        layout: &AppInfoLayout,
+        string_table: Option<&StringTable>,
+        options: &ReadOptions,
+        appinfo: &mut AppInfo,
+    ) -> Result<(), VdfrError> {
+        use rayon::prelude::*;
+
+        let mut raw_apps = Vec::new();
+        // An error that stopped the scan for entries, reported after those
+        // of the entries before it.
+        let mut fatal = None;
+
+        loop {
+            let offset = reader.position();
+            let app_id = match reader.read_u32::<LittleEndian>() {
+                Ok(0) => break,
+                Ok(app_id) => app_id,
+                Err(e) => {
+                    fatal = Some(VdfrError::from(e).at_offset(offset));
+                    break;
+                }
+            };
+
+            match AppInfo::read_raw_app(reader, layout) {
+                Ok((app, key_values)) => {
+                    let key_values_offset = reader.position() - key_values.len() as u64;
+                    raw_apps.push((app_id, offset, app, key_values, key_values_offset));
+                }
+                Err(e) => {
+                    fatal = Some(e.at_offset(offset).in_entry(Entry::App(app_id)));
+                    break;
+                }
+            }
+        }
+
+        let parsed: Vec<(u32, Result<App, VdfrError>)> = raw_apps
+            .into_par_iter()
+            .map_init(
+                || Keys::new(string_table),
+                |keys, (app_id, offset, mut app, key_values, key_values_offset)| {
+                    let result = binary::read_with_keys(
+                        &mut key_values.as_slice(),
+                        false,
+                        string_table,
+                        keys,
+                    )
+                    .map(|key_values| {
+                        app.key_values = key_values;
+                        app
+                    })
+                    .map_err(|e| {
+                        e.rebase(key_values_offset)
+                            .at_offset(offset)
+                            .in_entry(Entry::App(app_id))
+                    });
+                    (app_id, result)
+                },
+            )
+            .collect();
+
+        for (app_id, result) in parsed {
+            match result {
+                Ok(app) => {
+                    appinfo.apps.insert(app_id, app);
+                }
+                Err(e) if options.recover => appinfo.diagnostics.push(e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match fatal {
+            Some(e) if options.recover => appinfo.diagnostics.push(e),
+            Some(e) => return Err(e),
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    // Passes the key-values of every app to the visitor, without parsing
+    // them into a tree. Each app is reported as a node keyed by its ID.
+    pub fn visit<R: Read + Seek, V: for<'de> KvVisitor<'de> + ?Sized>(
+        reader: &mut R,
+        visitor: &mut V,
+    ) -> Result<(), VdfrError> {
+        let mut reader = Counter::from_current(reader)?;
+        let header = AppInfo::read_header(&mut reader)?;
+        let string_table = header.string_table.as_ref();
+
+        loop {
+            let offset = reader.position();
+            let app_id = reader
+                .read_u32::<LittleEndian>()
+                .map_err(|e| VdfrError::from(e).at_offset(offset))?;
             if app_id == 0 {
                 break;
             }
 
-            let size = reader.read_u32::<LittleEndian>()?;
-            let state = reader.read_u32::<LittleEndian>()?;
-            let last_update = reader.read_u32::<LittleEndian>()?;
-            let access_token = reader.read_u64::<LittleEndian>()?;
+            AppInfo::read_app_header(&mut reader, header.layout)
+                .and_then(|_| {
+                    let key_values_offset = reader.position();
+                    visitor.begin_object(app_id.to_string().into());
+                    binary::visit(&mut reader, false, string_table, visitor)
+                        .map_err(|e| e.rebase(key_values_offset))?;
+                    visitor.end_object();
+                    Ok(())
+                })
+                .map_err(|e| e.at_offset(offset).in_entry(Entry::App(app_id)))?;
+        }
+
+        Ok(())
+    }
 
-            let mut checksum_txt: [u8; 20] = [0; 20];
-            reader.read_exact(&mut checksum_txt)?;
+    fn read_app<R: Read>(
+        reader: &mut Counter<R>,
+        layout: &AppInfoLayout,
+        string_table: Option<&StringTable>,
+        keys: &mut Keys,
+    ) -> Result<App, VdfrError> {
+        let mut app = AppInfo::read_app_header(reader, layout)?;
+        let offset = reader.position();
+        app.key_values = binary::read_with_keys(reader, false, string_table, keys)
+            .map_err(|e| e.rebase(offset))?;
+        Ok(app)
+    }
 
-            let change_number = reader.read_u32::<LittleEndian>()?;
+    // Reads the fields of an app entry that follow its ID, leaving the
+    // reader at the start of its key-value data.
+    fn read_app_header<R: Read>(reader: &mut R, layout: &AppInfoLayout) -> Result<App, VdfrError> {
+        let size = reader.read_u32::<LittleEndian>()?;
+        let state = reader.read_u32::<LittleEndian>()?;
+        let last_update = reader.read_u32::<LittleEndian>()?;
+        let access_token = reader.read_u64::<LittleEndian>()?;
 
+        let mut checksum_txt: [u8; 20] = [0; 20];
+        reader.read_exact(&mut checksum_txt)?;
+
+        let change_number = reader.read_u32::<LittleEndian>()?;
+
+        let checksum_bin = if layout.checksum_bin {
             let mut checksum_bin: [u8; 20] = [0; 20];
             reader.read_exact(&mut checksum_bin)?;
+            Some(checksum_bin)
+        } else {
+            None
+        };
 
-            let key_values = read_kv(reader, false, &string_table)?;
-
-            let app = App {
-                size,
-                state,
-                last_update,
-                access_token,
-                checksum_txt,
-                checksum_bin,
-                change_number,
-                key_values,
-            };
-            appinfo.apps.insert(app_id, app);
+        Ok(App {
+            size,
+            state,
+            last_update,
+            access_token,
+            checksum_txt,
+            checksum_bin,
+            change_number,
+            key_values: KeyValues::new(),
+        })
+    }
+
+    // Reads an app entry without parsing its key-values, which are returned
+    // as stored.
+    fn read_raw_app<R: Read>(
+        reader: &mut R,
+        layout: &AppInfoLayout,
+    ) -> Result<(App, Vec<u8>), VdfrError> {
+        let app = AppInfo::read_app_header(reader, layout)?;
+        let key_values_size = app.key_values_size()?;
+
+        // Read through `take` rather than into a buffer of the declared size,
+        // so that a corrupt size can't make us allocate gigabytes up front.
+        let mut key_values: Vec<u8> = Vec::new();
+        reader
+            .take(key_values_size as u64)
+            .read_to_end(&mut key_values)?;
+        if key_values.len() != key_values_size {
+            return Err(VdfrError::UnexpectedEof);
         }
+        Ok((app, key_values))
+    }
 
-        Ok(appinfo)
+    // Recomputes the SHA-1 of every app's binary key-value data as stored in
+    // the file, returning the apps for which it doesn't match `checksum_bin`.
+    // Versions before 28 don't have binary checksums, which is an error
+    // rather than an empty list.
+    pub fn verify_checksum_bin<R: Read + Seek>(
+        reader: &mut R,
+    ) -> Result<Vec<ChecksumMismatch>, VdfrError> {
+        let mut reader = Counter::from_current(reader)?;
+        let header = AppInfo::read_header(&mut reader)?;
+        if !header.layout.checksum_bin {
+            return Err(VdfrError::NoChecksumBin(header.magic));
+        }
+
+        let mut mismatches = Vec::new();
+
+        loop {
+            let offset = reader.position();
+            let app_id = reader
+                .read_u32::<LittleEndian>()
+                .map_err(|e| VdfrError::from(e).at_offset(offset))?;
+            if app_id == 0 {
+                break;
+            }
+
+            let (app, key_values) = AppInfo::read_raw_app(&mut reader, header.layout)
+                .map_err(|e| e.at_offset(offset).in_entry(Entry::App(app_id)))?;
+
+            if let Some(expected) = app.checksum_bin {
+                let actual: [u8; 20] = Sha1::digest(&key_values).into();
+                if actual != expected {
+                    mismatches.push(ChecksumMismatch {
+                        app_id,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    // Reads the magic and universe, and the string table if the version has
+    // one, leaving the reader at the first app.
+    fn read_header<R: Read + Seek>(reader: &mut Counter<R>) -> Result<AppInfoHeader, VdfrError> {
+        let magic = reader.read_u32::<LittleEndian>()?;
+        let layout = AppInfoLayout::for_magic(magic)?;
+        let universe = reader.read_u32::<LittleEndian>()?;
+
+        let string_table = if layout.string_table {
+            Some(AppInfo::read_string_table(reader)?)
+        } else {
+            None
+        };
+
+        Ok(AppInfoHeader {
+            magic,
+            universe,
+            layout,
+            string_table,
+        })
     }
 
-    fn read_string_table(reader: &mut BufReader<File>) -> Result<Vec<String>, std::io::Error> {
-        let string_table_offset = reader.read_i64::<LittleEndian>()?;
+    fn read_string_table<R: Read + Seek>(
+        reader: &mut Counter<R>,
+    ) -> Result<StringTable, VdfrError> {
+        let offset = reader.position();
+        let string_table_offset = reader
+            .read_i64::<LittleEndian>()
+            .map_err(|e| VdfrError::from(e).at_offset(offset))?;
+        let string_table_offset =
+            AppInfo::string_table_offset(string_table_offset).map_err(|e| e.at_offset(offset))?;
         let original_seek_position = reader.stream_position()?;
-        reader.seek(std::io::SeekFrom::Start(string_table_offset as u64))?;
-        let num_strings = reader.read_u32::<LittleEndian>()?;
+        reader.seek(std::io::SeekFrom::Start(string_table_offset))?;
         let mut string_table_bytes: Vec<u8> = Vec::new();
         reader.read_to_end(&mut string_table_bytes)?;
-        let string_table: Vec<String> = string_table_bytes
-            .split(|&byte| byte == 0)
-            .filter(|subslice| !subslice.is_empty()) // Filter out any empty slices (if any)
-            .map(|subslice| String::from_utf8_lossy(subslice).into_owned()) // Convert each subslice to a String
-            .collect();
-        assert!(string_table.len() == num_strings as usize);
+        let string_table = AppInfo::parse_string_table(&string_table_bytes)
+            .map_err(|e| e.at_offset(string_table_offset))?;
         reader.seek(std::io::SeekFrom::Start(original_seek_position))?;
 
         Ok(string_table)
     }
+
+    // The header stores the string table offset as an i64, which can't be
+    // negative in a valid file.
+    pub(crate) fn string_table_offset(offset: i64) -> Result<u64, VdfrError> {
+        u64::try_from(offset).map_err(|_| VdfrError::InvalidStringTableOffset(offset))
+    }
+
+    // Parses a string table from its string count up to the end of the file.
+    fn parse_string_table(bytes: &[u8]) -> Result<StringTable, VdfrError> {
+        let strings: Vec<Key> = AppInfo::split_string_table(bytes)?
+            .into_iter()
+            .map(|subslice| Key::from(String::from_utf8_lossy(subslice)))
+            .collect();
+        Ok(StringTable::from(strings))
+    }
+
+    // Splits a string table into its strings, without decoding them.
+    pub(crate) fn split_string_table(mut bytes: &[u8]) -> Result<Vec<&[u8]>, VdfrError> {
+        let num_strings = bytes.read_u32::<LittleEndian>()?;
+        let mut strings: Vec<&[u8]> = bytes.split(|&byte| byte == 0).collect();
+        // Every string is null-terminated, leaving an empty slice after the last.
+        if strings.last().is_some_and(|s| s.is_empty()) {
+            strings.pop();
+        }
+        if strings.len() != num_strings as usize {
+            return Err(VdfrError::StringTableCountMismatch {
+                expected: num_strings,
+                actual: strings.len(),
+            });
+        }
+        Ok(strings)
+    }
+
+    // Writes the apps in the layout of `magic`. For v29 the string table read
+    // with the apps is written back as it was, with any new keys appended.
+    // `checksum_bin` is always recomputed from the key-values as written, if
+    // the layout has one, so that it can't go stale.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), VdfrError> {
+        let layout = AppInfoLayout::for_magic(self.magic)?;
+
+        let mut string_table = if layout.string_table {
+            Some(self.string_table.clone())
+        } else {
+            None
+        };
+
+        // The apps are serialized up front, since every entry is prefixed with
+        // its size and the v29 header points past all of them to the string
+        // table that gets built while writing their keys.
+        let mut apps: Vec<u8> = Vec::new();
+        for (app_id, app) in &self.apps {
+            let mut key_values: Vec<u8> = Vec::new();
+            binary::write(
+                &mut key_values,
+                &app.key_values,
+                false,
+                string_table.as_mut(),
+            )?;
+
+            let mut entry: Vec<u8> = Vec::new();
+            entry.write_u32::<LittleEndian>(app.state)?;
+            entry.write_u32::<LittleEndian>(app.last_update)?;
+            entry.write_u64::<LittleEndian>(app.access_token)?;
+            entry.write_all(&app.checksum_txt)?;
+            entry.write_u32::<LittleEndian>(app.change_number)?;
+            if layout.checksum_bin {
+                let checksum_bin: [u8; 20] = Sha1::digest(&key_values).into();
+                entry.write_all(&checksum_bin)?;
+            }
+            entry.write_all(&key_values)?;
+
+            apps.write_u32::<LittleEndian>(*app_id)?;
+            apps.write_u32::<LittleEndian>(entry.len() as u32)?;
+            apps.write_all(&entry)?;
+        }
+        apps.write_u32::<LittleEndian>(0)?;
+
+        writer.write_u32::<LittleEndian>(self.magic)?;
+        writer.write_u32::<LittleEndian>(self.universe)?;
+
+        if let Some(string_table) = &string_table {
+            // magic + universe + the offset itself
+            let string_table_offset = 4 + 4 + 8 + apps.len();
+            writer.write_i64::<LittleEndian>(string_table_offset as i64)?;
+            writer.write_all(&apps)?;
+            string_table.write(writer)?;
+        } else {
+            writer.write_all(&apps)?;
+        }
+
+        Ok(())
+    }
 }
 
+#[cfg(feature = "std")]
 impl App {
+    // Bytes of an entry counted by `size` that precede the key-value data,
+    // not including the binary checksum of newer versions.
+    const HEADER_SIZE: u32 = 4 + 4 + 8 + 20 + 4;
+
+    fn key_values_size(&self) -> Result<usize, VdfrError> {
+        let header_size = match self.checksum_bin {
+            Some(_) => App::HEADER_SIZE + 20,
+            None => App::HEADER_SIZE,
+        };
+        match self.size.checked_sub(header_size) {
+            Some(size) => Ok(size as usize),
+            None => Err(VdfrError::InvalidSize(self.size)),
+        }
+    }
+
     pub fn get(&self, keys: &[&str]) -> Option<&Value> {
         find_keys(&self.key_values, keys)
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Package {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::hex"))]
     pub checksum: [u8; 20],
     pub change_number: u32,
-    pub pics: u64,
+    pub pics: Option<u64>,
     pub key_values: KeyValues,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PackageInfo {
     pub magic: u32,
     pub universe: u32,
-    pub packages: HashMap<u32, Package>,
+    pub packages: IndexMap<u32, Package>,
+    // Errors for the entries skipped when reading with `ReadOptions::recover`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub diagnostics: Vec<VdfrError>,
 }
 
+#[cfg(feature = "std")]
 impl PackageInfo {
-    pub fn read(reader: &mut BufReader<File>) -> Result<PackageInfo, VdfrError> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<PackageInfo, VdfrError> {
+        PackageInfo::read(&mut Cursor::new(bytes))
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<PackageInfo, VdfrError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        PackageInfo::read(&mut reader)
+    }
+
+    // Reads the header of a packageinfo.vdf, returning an iterator over its
+    // packages in file order. Packages are parsed as the iterator is advanced
+    // and not kept.
+    pub fn iter_packages<R: Read>(reader: R) -> Result<PackageIter<R>, VdfrError> {
+        PackageIter::new(reader)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<PackageInfo, VdfrError> {
+        PackageInfo::read_with_options(reader, &ReadOptions::default())
+    }
+
+    // Packages don't declare their size, so with `ReadOptions::recover` there
+    // is no way past a corrupt one. Reading stops there instead, keeping the
+    // packages before it.
+    pub fn read_with_options<R: Read>(
+        reader: &mut R,
+        options: &ReadOptions,
+    ) -> Result<PackageInfo, VdfrError> {
+        let mut reader = Counter::new(reader, 0);
+
         let magic = reader.read_u32::<LittleEndian>()?;
+        let layout = PackageInfoLayout::for_magic(magic)?;
         let universe = reader.read_u32::<LittleEndian>()?;
 
         let mut packageinfo = PackageInfo {
             magic,
             universe,
-            packages: HashMap::new(),
+            packages: IndexMap::new(),
+            diagnostics: Vec::new(),
         };
+        let mut keys = Keys::default();
 
         loop {
-            let package_id = reader.read_u32::<LittleEndian>()?;
+            let offset = reader.position();
+            let result = reader
+                .read_u32::<LittleEndian>()
+                .map_err(|e| VdfrError::from(e).at_offset(offset))
+                .and_then(|package_id| {
+                    if package_id == 0xffffffff {
+                        return Ok(None);
+                    }
+                    PackageInfo::read_package(&mut reader, layout, &mut keys)
+                        .map(|package| Some((package_id, package)))
+                        .map_err(|e| e.at_offset(offset).in_entry(Entry::Package(package_id)))
+                });
 
-            if package_id == 0xffffffff {
-                break;
+            match result {
+                Ok(Some((package_id, package))) => {
+                    packageinfo.packages.insert(package_id, package);
+                }
+                Ok(None) => break,
+                Err(e) if options.recover => {
+                    packageinfo.diagnostics.push(e);
+                    break;
+                }
+                Err(e) => return Err(e),
             }
+        }
 
-            let mut checksum: [u8; 20] = [0; 20];
-            reader.read_exact(&mut checksum)?;
-
-            let change_number = reader.read_u32::<LittleEndian>()?;
-
-            // XXX: No idea what this is. Seems to get ignored in vdf.py.
-            let pics = reader.read_u64::<LittleEndian>()?;
+        Ok(packageinfo)
+    }
 
-            let key_values = read_kv(reader, false, &None)?;
+    fn read_package<R: Read>(
+        reader: &mut Counter<R>,
+        layout: &PackageInfoLayout,
+        keys: &mut Keys,
+    ) -> Result<Package, VdfrError> {
+        let mut checksum: [u8; 20] = [0; 20];
+        reader.read_exact(&mut checksum)?;
 
-            let package = Package {
-                checksum,
-                change_number,
-                pics,
-                key_values,
-            };
+        let change_number = reader.read_u32::<LittleEndian>()?;
 
-            packageinfo.packages.insert(package_id, package);
-        }
+        // XXX: No idea what this is. Seems to get ignored in vdf.py.
+        let pics = if layout.pics {
+            Some(reader.read_u64::<LittleEndian>()?)
+        } else {
+            None
+        };
 
-        Ok(packageinfo)
-    }
-}
+        let offset = reader.position();
+        let key_values =
+            binary::read_with_keys(reader, false, None, keys).map_err(|e| e.rebase(offset))?;
 
-impl Package {
-    pub fn get(&self, keys: &[&str]) -> Option<&Value> {
-        find_keys(&self.key_values, keys)
+        Ok(Package {
+            checksum,
+            change_number,
+            pics,
+            key_values,
+        })
     }
-}
 
-fn read_kv<R: std::io::Read>(
-    reader: &mut R,
-    alt_format: bool,
-    string_table: &Option<Vec<String>>,
-) -> Result<KeyValues, VdfrError> {
-    let current_bin_end = if alt_format { BIN_END_ALT } else { BIN_END };
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), VdfrError> {
+        let layout = PackageInfoLayout::for_magic(self.magic)?;
 
-    let mut node = KeyValues::new();
+        writer.write_u32::<LittleEndian>(self.magic)?;
+        writer.write_u32::<LittleEndian>(self.universe)?;
 
-    loop {
-        let t = reader.read_u8()?;
-        if t == current_bin_end {
-            return Ok(node);
+        for (package_id, package) in &self.packages {
+            writer.write_u32::<LittleEndian>(*package_id)?;
+            writer.write_all(&package.checksum)?;
+            writer.write_u32::<LittleEndian>(package.change_number)?;
+            if layout.pics {
+                writer.write_u64::<LittleEndian>(package.pics.unwrap_or(0))?;
+            }
+            binary::write(writer, &package.key_values, false, None)?;
         }
 
-        let key = if let Some(string_table) = string_table {
-            let string_table_index = reader.read_u32::<LittleEndian>()?;
-            string_table[string_table_index as usize].clone()
-        } else {
-            read_string(reader, false)?
-        };
+        writer.write_u32::<LittleEndian>(0xffffffff)?;
 
-        if t == BIN_NONE {
-            let subnode = read_kv(reader, alt_format, string_table)?;
-            node.insert(key, Value::KeyValueType(subnode));
-        } else if t == BIN_STRING {
-            let s = read_string(reader, false)?;
-            node.insert(key, Value::StringType(s));
-        } else if t == BIN_WIDESTRING {
-            let s = read_string(reader, true)?;
-            node.insert(key, Value::WideStringType(s));
-        } else if [BIN_INT32, BIN_POINTER, BIN_COLOR].contains(&t) {
-            let val = reader.read_i32::<LittleEndian>()?;
-            if t == BIN_INT32 {
-                node.insert(key, Value::Int32Type(val));
-            } else if t == BIN_POINTER {
-                node.insert(key, Value::PointerType(val));
-            } else if t == BIN_COLOR {
-                node.insert(key, Value::ColorType(val));
-            }
-        } else if t == BIN_UINT64 {
-            let val = reader.read_u64::<LittleEndian>()?;
-            node.insert(key, Value::UInt64Type(val));
-        } else if t == BIN_INT64 {
-            let val = reader.read_i64::<LittleEndian>()?;
-            node.insert(key, Value::Int64Type(val));
-        } else if t == BIN_FLOAT32 {
-            let val = reader.read_f32::<LittleEndian>()?;
-            node.insert(key, Value::Float32Type(val));
-        } else {
-            return Err(VdfrError::InvalidType(t));
-        }
+        Ok(())
     }
 }
 
-fn read_string<R: std::io::Read>(reader: &mut R, wide: bool) -> Result<String, Error> {
-    if wide {
-        let mut buf: Vec<u16> = vec![];
-        loop {
-            // Maybe this should be big-endian?
-            let c = reader.read_u16::<LittleEndian>()?;
-            if c == 0 {
-                break;
-            }
-            buf.push(c);
-        }
-        Ok(std::string::String::from_utf16_lossy(&buf).to_string())
-    } else {
-        let mut buf: Vec<u8> = vec![];
-        loop {
-            let c = reader.read_u8()?;
-            if c == 0 {
-                break;
-            }
-            buf.push(c);
-        }
-        Ok(std::string::String::from_utf8_lossy(&buf).to_string())
+#[cfg(feature = "std")]
+impl Package {
+    pub fn get(&self, keys: &[&str]) -> Option<&Value> {
+        find_keys(&self.key_values, keys)
     }
 }
 
+#[cfg(feature = "std")]
 pub fn print_keyvalues(keyvalues: &KeyValues, depth: usize) {
     for (key, value) in keyvalues {
         print!("{}{}: ", " ".repeat(depth), key);
@@ -345,3 +1243,649 @@ pub fn print_keyvalues(keyvalues: &KeyValues, depth: usize) {
         }
     }
 }
+
+// Keeps track of the position of a reader, so that errors can point at where
+// in the input they happened.
+#[cfg(feature = "std")]
+pub(crate) struct Counter<R> {
+    inner: R,
+    // Position in `inner` that positions and seeks are relative to.
+    start: u64,
+    position: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R> Counter<R> {
+    pub(crate) fn new(inner: R, position: u64) -> Counter<R> {
+        Counter {
+            inner,
+            start: 0,
+            position,
+        }
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Seek> Counter<R> {
+    // Counts from the current position of `inner`, so that offsets are
+    // relative to where reading started rather than to the start of `inner`.
+    pub(crate) fn from_current(mut inner: R) -> std::io::Result<Counter<R>> {
+        let start = inner.stream_position()?;
+        Ok(Counter {
+            inner,
+            start,
+            position: 0,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for Counter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Seek> Seek for Counter<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            std::io::SeekFrom::Start(offset) => std::io::SeekFrom::Start(self.start + offset),
+            pos => pos,
+        };
+        let position = self.inner.seek(pos)?;
+        self.position = position.checked_sub(self.start).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek to before where reading started",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use alloc::borrow::Cow;
+
+    fn app(key_values: KeyValues) -> App {
+        App {
+            size: 0,
+            state: 2,
+            last_update: 1700000000,
+            access_token: 0,
+            checksum_txt: [0xab; 20],
+            checksum_bin: None,
+            change_number: 12345,
+            key_values,
+        }
+    }
+
+    // A v29 file with one app whose key-values are
+    // `appinfo { name "x" }`, and a string table that isn't in the order the
+    // keys are used in and has an entry no key refers to.
+    fn v29_fixture() -> Vec<u8> {
+        let mut key_values = vec![binary::BIN_NONE];
+        key_values.extend(1u32.to_le_bytes());
+        key_values.push(binary::BIN_STRING);
+        key_values.extend(0u32.to_le_bytes());
+        key_values.extend(b"x\0");
+        key_values.push(binary::BIN_END);
+        key_values.push(binary::BIN_END);
+
+        let mut entry = Vec::new();
+        entry.extend(2u32.to_le_bytes());
+        entry.extend(0u32.to_le_bytes());
+        entry.extend(0u64.to_le_bytes());
+        entry.extend([0u8; 20]);
+        entry.extend(0u32.to_le_bytes());
+        entry.extend(Sha1::digest(&key_values));
+        entry.extend(&key_values);
+
+        let mut apps = Vec::new();
+        apps.extend(7u32.to_le_bytes());
+        apps.extend((entry.len() as u32).to_le_bytes());
+        apps.extend(&entry);
+        apps.extend(0u32.to_le_bytes());
+
+        let mut file = Vec::new();
+        file.extend(APPINFO_VERSION_29.to_le_bytes());
+        file.extend(1u32.to_le_bytes());
+        file.extend(((16 + apps.len()) as i64).to_le_bytes());
+        file.extend(&apps);
+        file.extend(3u32.to_le_bytes());
+        file.extend(b"name\0appinfo\0unused\0");
+        file
+    }
+
+    #[test]
+    fn appinfo_v28_round_trip() {
+        let key_values =
+            text::parse(r#""appinfo" { "appid" "440" "common" { "name" "Team Fortress 2" } }"#)
+                .unwrap();
+        let mut apps = IndexMap::new();
+        apps.insert(440, app(key_values.clone()));
+        apps.insert(570, app(key_values));
+        let appinfo = AppInfo {
+            magic: APPINFO_VERSION_28,
+            universe: 1,
+            apps,
+            string_table: StringTable::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let mut written = Vec::new();
+        appinfo.write(&mut written).unwrap();
+        assert!(AppInfo::verify_checksum_bin(&mut Cursor::new(&written))
+            .unwrap()
+            .is_empty());
+
+        let read = AppInfo::from_bytes(&written).unwrap();
+        assert_eq!(read.apps.keys().collect::<Vec<_>>(), [&440, &570]);
+        assert_eq!(read.apps[&440].key_values, appinfo.apps[&440].key_values);
+
+        let mut rewritten = Vec::new();
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(rewritten, written);
+    }
+
+    #[test]
+    fn appinfo_v29_round_trip_keeps_string_table() {
+        let original = v29_fixture();
+        assert!(AppInfo::verify_checksum_bin(&mut Cursor::new(&original))
+            .unwrap()
+            .is_empty());
+
+        let appinfo = AppInfo::from_bytes(&original).unwrap();
+        assert_eq!(
+            appinfo.apps[&7].get(&["appinfo", "name"]),
+            Some(&Value::StringType("x".to_string()))
+        );
+
+        let mut written = Vec::new();
+        appinfo.write(&mut written).unwrap();
+        assert_eq!(written, original);
+    }
+
+    #[test]
+    fn appinfo_v29_write_appends_new_keys() {
+        let mut appinfo = AppInfo::from_bytes(&v29_fixture()).unwrap();
+        if let Some(Value::KeyValueType(node)) = appinfo.apps[&7].key_values.get_mut("appinfo") {
+            node.push("type", Value::StringType("Game".to_string()));
+        }
+
+        let mut written = Vec::new();
+        appinfo.write(&mut written).unwrap();
+        assert!(AppInfo::verify_checksum_bin(&mut Cursor::new(&written))
+            .unwrap()
+            .is_empty());
+
+        let read = AppInfo::from_bytes(&written).unwrap();
+        assert_eq!(
+            read.string_table.iter().collect::<Vec<_>>(),
+            ["name", "appinfo", "unused", "type"]
+        );
+        assert_eq!(
+            read.apps[&7].get(&["appinfo", "type"]),
+            Some(&Value::StringType("Game".to_string()))
+        );
+    }
+
+    #[test]
+    fn verify_checksum_bin_reports_mismatches() {
+        let mut file = v28_fixture();
+        assert!(AppInfo::verify_checksum_bin(&mut Cursor::new(&file))
+            .unwrap()
+            .is_empty());
+
+        // Change a byte of the last app's key-values without touching its
+        // structure.
+        let end = file.len() - 8;
+        file[end] = b'2';
+        let mismatches = AppInfo::verify_checksum_bin(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].app_id, 730);
+        assert_ne!(mismatches[0].actual, mismatches[0].expected);
+
+        // A truncated entry is reported where it starts.
+        let last_entry = file.len() as u64 - 4 - (4 + 4 + 60 + 20);
+        file.truncate(file.len() - 10);
+        let err = AppInfo::verify_checksum_bin(&mut Cursor::new(&file)).unwrap_err();
+        assert_eq!(err.context().unwrap().entry, Some(Entry::App(730)));
+        assert_eq!(err.context().unwrap().offset, Some(last_entry));
+    }
+
+    #[test]
+    fn verify_checksum_bin_needs_v28() {
+        let appinfo = AppInfo {
+            magic: APPINFO_VERSION_27,
+            universe: 1,
+            apps: IndexMap::new(),
+            string_table: StringTable::new(),
+            diagnostics: Vec::new(),
+        };
+        let mut written = Vec::new();
+        appinfo.write(&mut written).unwrap();
+
+        let err = AppInfo::verify_checksum_bin(&mut Cursor::new(&written)).unwrap_err();
+        assert!(matches!(err, VdfrError::NoChecksumBin(APPINFO_VERSION_27)));
+    }
+
+    #[test]
+    fn appinfo_v29_keys_are_the_string_tables() {
+        fn first_key(key_values: &KeyValues) -> &Key {
+            &key_values.iter().next().unwrap().0
+        }
+
+        let file = v29_fixture();
+        let appinfo = AppInfo::from_bytes(&file).unwrap();
+        let table_key = appinfo.string_table.get(1).unwrap();
+        assert!(core::ptr::eq(
+            &**first_key(&appinfo.apps[&7].key_values),
+            table_key
+        ));
+
+        // The key-values start at offset 84.
+        let key_values =
+            binary::read(&mut &file[84..], false, Some(&appinfo.string_table)).unwrap();
+        assert!(core::ptr::eq(&**first_key(&key_values), table_key));
+    }
+
+    #[test]
+    fn appinfo_string_table_error_has_offset() {
+        let mut file = v29_fixture();
+        let string_table_offset = file.len() as u64 - 24;
+        file.truncate(file.len() - 7);
+
+        let err = AppInfo::from_bytes(&file).unwrap_err();
+        assert!(matches!(
+            err.inner(),
+            VdfrError::StringTableCountMismatch { .. }
+        ));
+        assert_eq!(err.context().unwrap().offset, Some(string_table_offset));
+    }
+
+    #[test]
+    fn appinfo_offsets_are_relative_to_start() {
+        let mut input = b"junk".to_vec();
+        input.extend(v29_fixture());
+
+        let mut cursor = Cursor::new(&input);
+        cursor.set_position(4);
+        let appinfo = AppInfo::read(&mut cursor).unwrap();
+        assert_eq!(appinfo.string_table.len(), 3);
+
+        let mut cursor = Cursor::new(&input);
+        cursor.set_position(4);
+        let mut index = AppInfoIndex::new(cursor).unwrap();
+        assert_eq!(
+            index.get(7).unwrap().unwrap().key_values,
+            appinfo.apps[&7].key_values
+        );
+
+        // Cut the string table short, which is reported at its own offset.
+        let string_table_offset = input.len() as u64 - 4 - 24;
+        input.truncate(input.len() - 7);
+        let mut cursor = Cursor::new(&input);
+        cursor.set_position(4);
+        let err = AppInfo::read(&mut cursor).unwrap_err();
+        assert_eq!(err.context().unwrap().offset, Some(string_table_offset));
+    }
+
+    #[test]
+    fn appinfo_negative_string_table_offset() {
+        let mut file = v29_fixture();
+        file[8..16].copy_from_slice(&(-5i64).to_le_bytes());
+
+        let err = AppInfo::from_bytes(&file).unwrap_err();
+        assert!(matches!(
+            err.inner(),
+            VdfrError::InvalidStringTableOffset(-5)
+        ));
+        assert_eq!(err.context().unwrap().offset, Some(8));
+
+        let err = borrowed::AppInfo::parse(&file).unwrap_err();
+        assert!(matches!(
+            err.inner(),
+            VdfrError::InvalidStringTableOffset(-5)
+        ));
+        assert_eq!(err.context().unwrap().offset, Some(8));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn appinfo_read_par_matches_read() {
+        let file = v29_fixture();
+        let read = AppInfo::read(&mut Cursor::new(&file)).unwrap();
+        let read_par = AppInfo::read_par(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(read_par.apps.keys().collect::<Vec<_>>(), [&7]);
+        assert_eq!(read_par.apps[&7].key_values, read.apps[&7].key_values);
+
+        // Only the parallel path relies on the declared size.
+        let mut file = file;
+        file[20..24].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(AppInfo::read(&mut Cursor::new(&file)).is_ok());
+        assert!(AppInfo::read_par(&mut Cursor::new(&file)).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_async_matches_read() {
+        let file = v29_fixture();
+        let read = AppInfo::from_bytes(&file).unwrap();
+        let read_async = AppInfo::read_async(&mut file.as_slice()).await.unwrap();
+        assert_eq!(read_async.apps.keys().collect::<Vec<_>>(), [&7]);
+        assert_eq!(read_async.apps[&7].key_values, read.apps[&7].key_values);
+        assert!(read_async.string_table.iter().eq(read.string_table.iter()));
+
+        // Unlike locating entries by their declared size, a wrong size
+        // doesn't matter, just as with `read`.
+        let mut file = file;
+        file[20..24].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(AppInfo::read_async(&mut file.as_slice()).await.is_ok());
+
+        let file = packageinfo_fixture();
+        let read = PackageInfo::from_bytes(&file).unwrap();
+        let read_async = PackageInfo::read_async(&mut file.as_slice()).await.unwrap();
+        assert_eq!(
+            read_async.packages.keys().collect::<Vec<_>>(),
+            read.packages.keys().collect::<Vec<_>>()
+        );
+    }
+
+    // Rebuilds a tree as text from visitor calls, to compare `visit` with
+    // `read`.
+    #[derive(Default)]
+    struct TextVisitor(String);
+
+    impl<'de> KvVisitor<'de> for TextVisitor {
+        fn begin_object(&mut self, key: Cow<'de, str>) {
+            self.0 += &format!("{} {{ ", key);
+        }
+
+        fn end_object(&mut self) {
+            self.0 += "} ";
+        }
+
+        fn string(&mut self, key: Cow<'de, str>, value: Cow<'de, str>) {
+            self.0 += &format!("{} {} ", key, value);
+        }
+    }
+
+    #[test]
+    fn read_visit_and_borrowed_agree() {
+        let file = v29_fixture();
+        let appinfo = AppInfo::from_bytes(&file).unwrap();
+        let borrowed = borrowed::AppInfo::parse(&file).unwrap();
+        assert_eq!(
+            borrowed.apps[&7].key_values.clone().into_owned(),
+            appinfo.apps[&7].key_values
+        );
+
+        let mut visitor = TextVisitor::default();
+        AppInfo::visit(&mut Cursor::new(&file), &mut visitor).unwrap();
+        assert_eq!(visitor.0, "7 { appinfo { name x } } ");
+
+        // An unknown type inside the nested node.
+        let mut file = file;
+        file[89] = 0x42;
+        let err = AppInfo::from_bytes(&file).unwrap_err();
+        assert!(matches!(err.inner(), VdfrError::InvalidType(0x42)));
+        assert_eq!(err.context().unwrap().path, ["appinfo", "name"]);
+        assert_eq!(
+            borrowed::AppInfo::parse(&file).unwrap_err().to_string(),
+            err.to_string()
+        );
+    }
+
+    // A v28 file with three apps, written out of ID order.
+    fn v28_fixture() -> Vec<u8> {
+        let key_values = text::parse(r#""appinfo" { "appid" "1" }"#).unwrap();
+        let mut apps = IndexMap::new();
+        for app_id in [570, 440, 730] {
+            apps.insert(app_id, app(key_values.clone()));
+        }
+        let appinfo = AppInfo {
+            magic: APPINFO_VERSION_28,
+            universe: 1,
+            apps,
+            string_table: StringTable::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let mut written = Vec::new();
+        appinfo.write(&mut written).unwrap();
+        written
+    }
+
+    #[test]
+    fn iter_apps_in_file_order() {
+        let file = v28_fixture();
+        let ids: Vec<u32> = AppInfo::iter_apps(Cursor::new(&file))
+            .unwrap()
+            .map(|item| item.unwrap().0)
+            .collect();
+        assert_eq!(ids, [570, 440, 730]);
+
+        // Nothing past the terminating zero ID is read.
+        let mut file = file;
+        file.extend(b"junk");
+        assert_eq!(AppInfo::iter_apps(Cursor::new(&file)).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn iter_apps_stops_after_error() {
+        // Replace the end of the last app's key-values with an unknown type.
+        let mut file = v28_fixture();
+        let end = file.len() - 5;
+        file[end] = 0x42;
+
+        let mut iter = AppInfo::iter_apps(Cursor::new(&file)).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().0, 570);
+        assert_eq!(iter.next().unwrap().unwrap().0, 440);
+        let err = iter.next().unwrap().unwrap_err();
+        assert!(matches!(err.inner(), VdfrError::InvalidType(0x42)));
+        assert_eq!(err.context().unwrap().entry, Some(Entry::App(730)));
+        assert!(iter.next().is_none());
+
+        // Stopping early never reaches the corrupt app.
+        let ids: Vec<u32> = AppInfo::iter_apps(Cursor::new(&file))
+            .unwrap()
+            .take(2)
+            .map(|item| item.unwrap().0)
+            .collect();
+        assert_eq!(ids, [570, 440]);
+    }
+
+    // A v28 file with three packages, written out of ID order. Package 0 is
+    // a real ID, unlike app 0.
+    fn packageinfo_fixture() -> Vec<u8> {
+        let key_values = text::parse(r#""packageid" "1""#).unwrap();
+        let mut packages = IndexMap::new();
+        for package_id in [5, 0, 9] {
+            packages.insert(
+                package_id,
+                Package {
+                    checksum: [0xcd; 20],
+                    change_number: 999,
+                    pics: Some(42),
+                    key_values: key_values.clone(),
+                },
+            );
+        }
+        let packageinfo = PackageInfo {
+            magic: PACKAGEINFO_VERSION_28,
+            universe: 1,
+            packages,
+            diagnostics: Vec::new(),
+        };
+
+        let mut written = Vec::new();
+        packageinfo.write(&mut written).unwrap();
+        written
+    }
+
+    #[test]
+    fn iter_packages_in_file_order() {
+        let mut file = packageinfo_fixture();
+        file.extend(b"junk");
+        let ids: Vec<u32> = PackageInfo::iter_packages(Cursor::new(&file))
+            .unwrap()
+            .map(|item| item.unwrap().0)
+            .collect();
+        assert_eq!(ids, [5, 0, 9]);
+    }
+
+    #[test]
+    fn iter_packages_stops_after_error() {
+        // Replace the type of the last package's only value, 14 bytes of
+        // key-values before the terminator, with an unknown one.
+        let mut file = packageinfo_fixture();
+        let start = file.len() - 4 - 14;
+        file[start] = 0x42;
+
+        let mut iter = PackageInfo::iter_packages(Cursor::new(&file)).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().0, 5);
+        assert_eq!(iter.next().unwrap().unwrap().0, 0);
+        let err = iter.next().unwrap().unwrap_err();
+        assert!(matches!(err.inner(), VdfrError::InvalidType(0x42)));
+        assert_eq!(err.context().unwrap().entry, Some(Entry::Package(9)));
+        assert!(iter.next().is_none());
+
+        let ids: Vec<u32> = PackageInfo::iter_packages(Cursor::new(&file))
+            .unwrap()
+            .take(2)
+            .map(|item| item.unwrap().0)
+            .collect();
+        assert_eq!(ids, [5, 0]);
+    }
+
+    #[test]
+    fn recover_skips_corrupt_app() {
+        // Give the value in the middle app's key-values an unknown type. Its
+        // entry starts after the header and the first 88-byte entry, and its
+        // key-values 68 bytes into the entry.
+        let mut file = v28_fixture();
+        file[8 + 88 + 68 + 9] = 0x42;
+
+        assert!(AppInfo::from_bytes(&file).is_err());
+
+        let options = ReadOptions { recover: true };
+        let appinfo = AppInfo::read_with_options(&mut Cursor::new(&file), &options).unwrap();
+        assert_eq!(appinfo.apps.keys().collect::<Vec<_>>(), [&570, &730]);
+        assert_eq!(appinfo.diagnostics.len(), 1);
+        let diagnostic = &appinfo.diagnostics[0];
+        assert!(matches!(diagnostic.inner(), VdfrError::InvalidType(0x42)));
+        let context = diagnostic.context().unwrap();
+        assert_eq!(context.entry, Some(Entry::App(440)));
+        assert_eq!(context.path, ["appinfo", "appid"]);
+        assert_eq!(context.offset, Some(8 + 88 + 68 + 9));
+    }
+
+    #[test]
+    fn recover_stops_at_truncated_app() {
+        let mut file = v28_fixture();
+        file.truncate(file.len() - 10);
+
+        let options = ReadOptions { recover: true };
+        let appinfo = AppInfo::read_with_options(&mut Cursor::new(&file), &options).unwrap();
+        assert_eq!(appinfo.apps.keys().collect::<Vec<_>>(), [&570, &440]);
+        assert_eq!(appinfo.diagnostics.len(), 1);
+        assert!(matches!(
+            appinfo.diagnostics[0].inner(),
+            VdfrError::UnexpectedEof
+        ));
+        assert_eq!(
+            appinfo.diagnostics[0].context().unwrap().entry,
+            Some(Entry::App(730))
+        );
+    }
+
+    #[test]
+    fn recover_stops_at_corrupt_package() {
+        // Packages are 50 bytes here, and the value's type is 36 bytes into
+        // each. Corrupt the middle one.
+        let mut file = packageinfo_fixture();
+        file[8 + 50 + 36] = 0x42;
+
+        assert!(PackageInfo::from_bytes(&file).is_err());
+
+        let options = ReadOptions { recover: true };
+        let packageinfo =
+            PackageInfo::read_with_options(&mut Cursor::new(&file), &options).unwrap();
+        assert_eq!(packageinfo.packages.keys().collect::<Vec<_>>(), [&5]);
+        assert_eq!(packageinfo.diagnostics.len(), 1);
+        assert_eq!(
+            packageinfo.diagnostics[0].context().unwrap().entry,
+            Some(Entry::Package(0))
+        );
+    }
+
+    #[test]
+    fn deep_binary_nesting_is_an_error() {
+        let mut bytes = Vec::new();
+        for _ in 0..200_000 {
+            bytes.push(binary::BIN_NONE);
+            bytes.extend(b"a\0");
+        }
+
+        let err = binary::read(&mut bytes.as_slice(), false, None).unwrap_err();
+        assert!(matches!(err.inner(), VdfrError::NestingTooDeep));
+        assert_eq!(err.context().unwrap().path.len(), MAX_DEPTH + 1);
+        assert_eq!(err.context().unwrap().offset, Some(MAX_DEPTH as u64 * 3));
+
+        let err = borrowed::read(&bytes, false, None).unwrap_err();
+        assert!(matches!(err.inner(), VdfrError::NestingTooDeep));
+    }
+
+    #[test]
+    fn packageinfo_round_trip() {
+        let key_values =
+            text::parse(r#""packageid" "5" "billingtype" "10" "appids" { "0" "440" "1" "570" }"#)
+                .unwrap();
+
+        for (magic, pics) in [
+            (PACKAGEINFO_VERSION_27, None),
+            (PACKAGEINFO_VERSION_28, Some(42)),
+        ] {
+            let mut packages = IndexMap::new();
+            for package_id in [0, 5] {
+                packages.insert(
+                    package_id,
+                    Package {
+                        checksum: [0xcd; 20],
+                        change_number: 999,
+                        pics,
+                        key_values: key_values.clone(),
+                    },
+                );
+            }
+            let packageinfo = PackageInfo {
+                magic,
+                universe: 1,
+                packages,
+                diagnostics: Vec::new(),
+            };
+
+            let mut written = Vec::new();
+            packageinfo.write(&mut written).unwrap();
+            assert_eq!(written[written.len() - 4..], [0xff; 4]);
+
+            let read = PackageInfo::from_bytes(&written).unwrap();
+            assert_eq!(read.packages.keys().collect::<Vec<_>>(), [&0, &5]);
+            assert_eq!(read.packages[&5].pics, pics);
+            assert_eq!(read.packages[&5].key_values, key_values);
+
+            let mut rewritten = Vec::new();
+            read.write(&mut rewritten).unwrap();
+            assert_eq!(rewritten, written);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+
+use crate::{KeyValues, Value};
+
+// Nodes are serialized as maps in their original order. Repeated keys are
+// written as repeated map entries, which not every format can represent.
+impl Serialize for KeyValues {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(&**key, value)?;
+        }
+        map.end()
+    }
+}
+
+// Serializes values as their plain contents, without the variant tag that
+// `Value` is normally serialized with, e.g. for dumping key-values as JSON.
+pub struct Untagged<'a, T: ?Sized>(pub &'a T);
+
+impl Serialize for Untagged<'_, Value> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::StringType(s) | Value::WideStringType(s) => serializer.serialize_str(s),
+            Value::Int32Type(v) | Value::PointerType(v) | Value::ColorType(v) => {
+                serializer.serialize_i32(*v)
+            }
+            Value::UInt64Type(v) => serializer.serialize_u64(*v),
+            Value::Int64Type(v) => serializer.serialize_i64(*v),
+            Value::Float32Type(v) => serializer.serialize_f32(*v),
+            Value::KeyValueType(kv) => Untagged(kv).serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Untagged<'_, KeyValues> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in self.0 {
+            map.serialize_entry(&**key, &Untagged(value))?;
+        }
+        map.end()
+    }
+}
+
+pub(crate) fn hex<S: Serializer>(checksum: &[u8; 20], serializer: S) -> Result<S::Ok, S::Error> {
+    let hex: String = checksum.iter().map(|b| format!("{:02x}", b)).collect();
+    serializer.serialize_str(&hex)
+}
+
+pub(crate) fn hex_option<S: Serializer>(
+    checksum: &Option<[u8; 20]>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match checksum {
+        Some(checksum) => hex(checksum, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+    use serde_json::json;
+
+    #[test]
+    fn checksums_serialize_as_hex() {
+        let mut app = App {
+            size: 0,
+            state: 2,
+            last_update: 0,
+            access_token: 0,
+            checksum_txt: [0xab; 20],
+            checksum_bin: Some([0x01; 20]),
+            change_number: 0,
+            key_values: KeyValues::new(),
+        };
+
+        let json = serde_json::to_value(&app).unwrap();
+        assert_eq!(json["checksum_txt"], json!("ab".repeat(20)));
+        assert_eq!(json["checksum_bin"], json!("01".repeat(20)));
+
+        app.checksum_bin = None;
+        let json = serde_json::to_value(&app).unwrap();
+        assert_eq!(json["checksum_bin"], json!(null));
+    }
+
+    #[test]
+    fn tagged_values_keep_their_type() {
+        let mut key_values = KeyValues::new();
+        key_values.push("int32", Value::Int32Type(5));
+        key_values.push("int64", Value::Int64Type(5));
+        key_values.push("pointer", Value::PointerType(5));
+
+        assert_eq!(
+            serde_json::to_string(&key_values).unwrap(),
+            r#"{"int32":{"Int32Type":5},"int64":{"Int64Type":5},"pointer":{"PointerType":5}}"#
+        );
+    }
+
+    #[test]
+    fn untagged_values_are_plain() {
+        let mut inner = KeyValues::new();
+        inner.push("name", Value::StringType("x".to_string()));
+        let mut key_values = KeyValues::new();
+        key_values.push("int32", Value::Int32Type(5));
+        key_values.push("int64", Value::Int64Type(-5));
+        key_values.push("common", Value::KeyValueType(inner));
+
+        assert_eq!(
+            serde_json::to_string(&Untagged(&key_values)).unwrap(),
+            r#"{"int32":5,"int64":-5,"common":{"name":"x"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Untagged(&Value::UInt64Type(7))).unwrap(),
+            "7"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    io::{Read, Write},
+    iter::Peekable,
+    str::Chars,
+};
+
+use crate::{KeyValues, Value, VdfrError, MAX_DEPTH};
+
+#[derive(Debug)]
+enum Token {
+    String(String),
+    OpenBrace,
+    CloseBrace,
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+        Parser {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, line: usize, column: usize, message: impl Into<String>) -> VdfrError {
+        VdfrError::SyntaxError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    // Skips whitespace and `//` comments.
+    fn skip_trivia(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' {
+                let mut lookahead = self.chars.clone();
+                lookahead.next();
+                if lookahead.peek() != Some(&'/') {
+                    return;
+                }
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    // Returns the next token along with the line and column it starts at.
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, VdfrError> {
+        self.skip_trivia();
+
+        let (line, column) = (self.line, self.column);
+        let token = match self.chars.peek() {
+            None => return Ok(None),
+            Some('{') => {
+                self.bump();
+                Token::OpenBrace
+            }
+            Some('}') => {
+                self.bump();
+                Token::CloseBrace
+            }
+            Some('"') => {
+                self.bump();
+                Token::String(self.read_quoted(line, column)?)
+            }
+            // Valve's platform conditionals, as in `"key" "value" [$WIN32]`.
+            Some('[') => {
+                return Err(self.error(line, column, "Conditionals like [$WIN32] aren't supported"))
+            }
+            Some(_) => Token::String(self.read_unquoted()),
+        };
+
+        Ok(Some((token, line, column)))
+    }
+
+    fn read_quoted(&mut self, line: usize, column: usize) -> Result<String, VdfrError> {
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error(line, column, "Unterminated string")),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('\\') => s.push('\\'),
+                    Some('"') => s.push('"'),
+                    // Unknown escapes are kept as written.
+                    Some(c) => {
+                        s.push('\\');
+                        s.push(c);
+                    }
+                    None => return Err(self.error(line, column, "Unterminated string")),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn read_unquoted(&mut self) -> String {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                break;
+            }
+            self.bump();
+            s.push(c);
+        }
+        s
+    }
+
+    // Parses key-value pairs until the end of the input, or until the closing
+    // brace of the current block if `depth` is above zero.
+    fn parse_node(&mut self, depth: usize) -> Result<KeyValues, VdfrError> {
+        let nested = depth > 0;
+        let mut node = KeyValues::new();
+
+        loop {
+            let key = match self.next_token()? {
+                Some((Token::String(key), _, _)) => key,
+                Some((Token::CloseBrace, _, _)) if nested => return Ok(node),
+                None if !nested => return Ok(node),
+                Some((Token::CloseBrace, line, column)) => {
+                    return Err(self.error(line, column, "Unexpected '}'"))
+                }
+                Some((Token::OpenBrace, line, column)) => {
+                    return Err(self.error(line, column, "Expected a key, found '{'"))
+                }
+                None => return Err(self.error(self.line, self.column, "Unexpected end of input")),
+            };
+
+            let value = match self.next_token()? {
+                Some((Token::String(s), _, _)) => Value::StringType(s),
+                Some((Token::OpenBrace, line, column)) => {
+                    if depth == MAX_DEPTH {
+                        return Err(self.error(line, column, "Blocks are nested too deeply"));
+                    }
+                    Value::KeyValueType(self.parse_node(depth + 1)?)
+                }
+                Some((Token::CloseBrace, line, column)) => {
+                    return Err(self.error(
+                        line,
+                        column,
+                        format!("Expected a value for key \"{}\", found '}}'", key),
+                    ))
+                }
+                None => {
+                    return Err(self.error(
+                        self.line,
+                        self.column,
+                        format!("Expected a value for key \"{}\"", key),
+                    ))
+                }
+            };
+
+            node.push(key, value);
+        }
+    }
+}
+
+// Parses text KeyValues (KV1), as used by libraryfolders.vdf, config.vdf and
+// appmanifest_*.acf. All values are read as `Value::StringType`.
+pub fn parse(input: &str) -> Result<KeyValues, VdfrError> {
+    Parser::new(input).parse_node(0)
+}
+
+pub fn read<R: Read>(reader: &mut R) -> Result<KeyValues, VdfrError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    parse(&input)
+}
+
+// Writes key-values as Valve-style text KeyValues, indented with tabs. Values
+// that aren't strings are written in their decimal form.
+pub fn write<W: Write>(writer: &mut W, key_values: &KeyValues) -> Result<(), VdfrError> {
+    write_node(writer, key_values, 0)?;
+    Ok(())
+}
+
+pub fn to_string(key_values: &KeyValues) -> String {
+    let mut buf: Vec<u8> = Vec::new();
+    write_node(&mut buf, key_values, 0).expect("writing to a Vec can't fail");
+    String::from_utf8(buf).expect("text KeyValues are always valid UTF-8")
+}
+
+fn write_node<W: Write>(
+    writer: &mut W,
+    key_values: &KeyValues,
+    depth: usize,
+) -> std::io::Result<()> {
+    let indent = "\t".repeat(depth);
+    for (key, value) in key_values {
+        let value = match value {
+            Value::KeyValueType(subnode) => {
+                writeln!(writer, "{}\"{}\"", indent, escape(key))?;
+                writeln!(writer, "{}{{", indent)?;
+                write_node(writer, subnode, depth + 1)?;
+                writeln!(writer, "{}}}", indent)?;
+                continue;
+            }
+            Value::StringType(s) | Value::WideStringType(s) => escape(s),
+            Value::Int32Type(v) | Value::PointerType(v) | Value::ColorType(v) => v.to_string(),
+            Value::UInt64Type(v) => v.to_string(),
+            Value::Int64Type(v) => v.to_string(),
+            Value::Float32Type(v) => format!("{:.6}", v),
+        };
+        writeln!(writer, "{}\"{}\"\t\t\"{}\"", indent, escape(key), value)?;
+    }
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::StringType(s.to_string())
+    }
+
+    fn syntax_error(input: &str) -> (usize, usize, String) {
+        match parse(input).unwrap_err() {
+            VdfrError::SyntaxError {
+                line,
+                column,
+                message,
+            } => (line, column, message),
+            e => panic!("expected a syntax error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn parse_quoted_and_unquoted_tokens() {
+        let kv = parse("\"quoted key\" \"quoted value\"\nunquoted 123\n\"mixed\"\tvalue").unwrap();
+        assert_eq!(kv.get("quoted key"), Some(&string("quoted value")));
+        assert_eq!(kv.get("unquoted"), Some(&string("123")));
+        assert_eq!(kv.get("mixed"), Some(&string("value")));
+    }
+
+    #[test]
+    fn parse_escapes() {
+        let kv = parse(r#""k" "a\nb\tc\\d\"e\qf""#).unwrap();
+        assert_eq!(kv.get("k"), Some(&string("a\nb\tc\\d\"e\\qf")));
+    }
+
+    #[test]
+    fn parse_comments() {
+        let input = "// leading comment\n\"a\" \"1\" // trailing comment\n\"b\" // between\n\"2\"";
+        let kv = parse(input).unwrap();
+        assert_eq!(kv.len(), 2);
+        assert_eq!(kv.get("a"), Some(&string("1")));
+        assert_eq!(kv.get("b"), Some(&string("2")));
+
+        // Slashes inside tokens aren't comments.
+        let kv = parse(r#""url" "http://example.com" path a/b"#).unwrap();
+        assert_eq!(kv.get("url"), Some(&string("http://example.com")));
+        assert_eq!(kv.get("path"), Some(&string("a/b")));
+    }
+
+    #[test]
+    fn parse_nested_blocks() {
+        let input = "\"libraryfolders\"\n{\n\t\"0\"\n\t{\n\t\t\"path\"\t\t\"C:\\\\Steam\"\n\t\t\"apps\" { }\n\t}\n}\n";
+        let kv = parse(input).unwrap();
+        let Some(Value::KeyValueType(folders)) = kv.get("libraryfolders") else {
+            panic!("libraryfolders should be a block");
+        };
+        let Some(Value::KeyValueType(folder)) = folders.get("0") else {
+            panic!("0 should be a block");
+        };
+        assert_eq!(folder.get("path"), Some(&string("C:\\Steam")));
+        assert_eq!(
+            folder.get("apps"),
+            Some(&Value::KeyValueType(KeyValues::new()))
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        // Reported where the string starts.
+        assert_eq!(
+            syntax_error("\"a\" \"b\"\n  \"c"),
+            (2, 3, "Unterminated string".to_string())
+        );
+        assert_eq!(
+            syntax_error("\"a\" \"b\"\n}"),
+            (2, 1, "Unexpected '}'".to_string())
+        );
+        // Reported at the end of the input.
+        assert_eq!(
+            syntax_error("\"a\"\n{\n\t\"b\" \"c\"\n"),
+            (4, 1, "Unexpected end of input".to_string())
+        );
+        assert_eq!(
+            syntax_error("\"a\" \"b\"\n\"c\""),
+            (2, 4, "Expected a value for key \"c\"".to_string())
+        );
+    }
+
+    #[test]
+    fn conditionals_are_rejected() {
+        let (line, column, message) = syntax_error("\"a\" \"b\" [$WIN32]");
+        assert_eq!((line, column), (1, 9));
+        assert!(message.contains("Conditionals"));
+    }
+
+    #[test]
+    fn write_indents_with_tabs() {
+        let mut inner = KeyValues::new();
+        inner.push("name", string("x"));
+        inner.push("id", Value::Int32Type(-5));
+        inner.push("ratio", Value::Float32Type(0.5));
+        let mut kv = KeyValues::new();
+        kv.push("appinfo", Value::KeyValueType(inner));
+        kv.push("empty", Value::KeyValueType(KeyValues::new()));
+        kv.push("size", Value::UInt64Type(7));
+
+        assert_eq!(
+            to_string(&kv),
+            "\"appinfo\"\n{\n\t\"name\"\t\t\"x\"\n\t\"id\"\t\t\"-5\"\n\t\"ratio\"\t\t\"0.500000\"\n}\n\
+             \"empty\"\n{\n}\n\
+             \"size\"\t\t\"7\"\n"
+        );
+
+        let mut written = Vec::new();
+        write(&mut written, &kv).unwrap();
+        assert_eq!(written, to_string(&kv).into_bytes());
+    }
+
+    #[test]
+    fn write_escapes() {
+        let mut kv = KeyValues::new();
+        kv.push("a\"b", string("back\\slash \"quote\" new\nline\ttab"));
+        assert_eq!(
+            to_string(&kv),
+            "\"a\\\"b\"\t\t\"back\\\\slash \\\"quote\\\" new\\nline\\ttab\"\n"
+        );
+    }
+
+    #[test]
+    fn write_then_parse_round_trips() {
+        let mut inner = KeyValues::new();
+        inner.push("0", string("440"));
+        inner.push("0", string("duplicate"));
+        inner.push("path", string("C:\\Steam \"quoted\"\n\t"));
+        let mut kv = KeyValues::new();
+        kv.push("apps", Value::KeyValueType(inner));
+        kv.push("apps", Value::KeyValueType(KeyValues::new()));
+        kv.push("last", string(""));
+
+        assert_eq!(parse(&to_string(&kv)).unwrap(), kv);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let input = "\"a\" {".repeat(200_000);
+        let (line, column, message) = syntax_error(&input);
+        assert_eq!(line, 1);
+        assert_eq!(column, MAX_DEPTH * 5 + 5);
+        assert_eq!(message, "Blocks are nested too deeply");
+
+        let input = "\"a\" {".repeat(MAX_DEPTH) + &"}".repeat(MAX_DEPTH);
+        assert!(parse(&input).is_ok());
+    }
+}